@@ -83,6 +83,11 @@ fn main() -> ! {
 
     // UART ---------------------------------------------------------
 
+    #[cfg(feature = "uart")]
+    let uart = dp.USART1.init::<OS>(&mut mcu);
+    #[cfg(feature = "uart-it")]
+    let uart_dispatcher = uart.get_interrupt_dispatcher();
+
     #[cfg(feature = "uart")]
     let (Some(uart_tx), Some(uart_rx)) = ({
         let pin_tx = gpioa.pa9;
@@ -92,9 +97,7 @@ fn main() -> ! {
         // let pin_rx = hal::afio::NONE_PIN;
 
         let config = uart::Config::default();
-        dp.USART1
-            .init::<OS>(&mut mcu)
-            .into_tx_rx((pin_tx, pin_rx), config, &mut mcu)
+        uart.into_tx_rx((pin_tx, pin_rx), config, &mut mcu)
     }) else {
         panic!()
     };
@@ -113,12 +116,10 @@ fn main() -> ! {
 
     #[cfg(feature = "uart-it")]
     let (tx, rx) = {
-        let (tx, mut tx_it) = uart_tx.into_interrupt(32, 0.micros());
-        let (rx, mut rx_it) = uart_rx.into_interrupt(64, 100.micros());
-        its::USART1_CB.set(&mut mcu, move || {
-            rx_it.handler();
-            tx_it.handler();
-        });
+        let (tx, tx_it) = uart_tx.into_interrupt(32, 0.micros());
+        let (rx, rx_it) = uart_rx.into_interrupt(64, 100.micros());
+        let mut dispatcher = uart_dispatcher.with_tx(tx_it).with_rx(rx_it);
+        its::USART1_CB.set(&mut mcu, move || dispatcher.handler());
         (tx, rx)
     };
 
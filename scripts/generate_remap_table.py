@@ -105,7 +105,12 @@ def write_binder_type(d: dict, filter: str, w: Write) -> None:
         name = func_pin_name(filter, func)
         if filter == "I2C" or name in ["SpiSckPin", "SpiNssPin"]:
             w.write(BIND_ALT_TYPE.format(func=name) + "}")
-        elif name.startswith("TimCh") or name in ["UartTxPin", "UartCkPin", "SpiMosiPin"]:
+        elif name.startswith("TimCh") or name in [
+            "UartTxPin",
+            "UartCkPin",
+            "UartRtsPin",
+            "SpiMosiPin",
+        ]:
             w.write(BIND_ALT_TYPE.format(func=name) + BIND_IS_PIN)
             w.write(f"impl_for_none_pin_into!({name});")
         elif filter in ["UART", "TIM"]:
@@ -127,7 +132,7 @@ BIND_PIN = """impl {func}<{remap}<{peri}>> for {pin}<Input> {{
 
 IMPL_TEMPLATE_LIST = [
     (
-        ["UartRxPin", "SpiMisoPin"],
+        ["UartRxPin", "UartCtsPin", "SpiMisoPin"],
         "impl<UP: UpMode> {func}<{remap}<{peri}>> for {pin}<Input<UP>>{{}}",
         "",
     ),
@@ -135,6 +140,7 @@ IMPL_TEMPLATE_LIST = [
         [
             "UartTxPin",
             "UartCkPin",
+            "UartRtsPin",
             "TimCh1Pin",
             "TimCh2Pin",
             "TimCh3Pin",
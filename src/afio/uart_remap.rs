@@ -53,11 +53,13 @@ pub trait UartCtsPin<REMAP> {
 }
 impl_for_none_pin!(UartCtsPin);
 pub trait UartRtsPin<REMAP> {
+    type P;
+    fn into_alternate(self) -> Self::P;
     fn is_pin(&self) -> bool {
         true
     }
 }
-impl_for_none_pin!(UartRtsPin);
+impl_for_none_pin_into!(UartRtsPin);
 pub trait UartRxPin<REMAP> {
     fn is_pin(&self) -> bool {
         true
@@ -89,6 +91,19 @@ impl UartTxPin<RemapDefault<UART5>> for PC12<Input> {
         self.into_mode(&mut Cr)
     }
 }
+impl UartCkPin<RemapDefault<USART1>> for PA8<Input> {
+    type P = PA8<Alternate<PushPull>>;
+    fn into_alternate(self) -> Self::P {
+        self.into_mode(&mut Cr)
+    }
+}
+impl<UP: UpMode> UartCtsPin<RemapDefault<USART1>> for PA11<Input<UP>> {}
+impl UartRtsPin<RemapDefault<USART1>> for PA12<Input> {
+    type P = PA12<Alternate<PushPull>>;
+    fn into_alternate(self) -> Self::P {
+        self.into_mode(&mut Cr)
+    }
+}
 impl<UP: UpMode> UartRxPin<RemapDefault<USART1>> for PA10<Input<UP>> {}
 impl UartTxPin<RemapDefault<USART1>> for PA9<Input> {
     type P = PA9<Alternate<PushPull>>;
@@ -96,6 +111,19 @@ impl UartTxPin<RemapDefault<USART1>> for PA9<Input> {
         self.into_mode(&mut Cr)
     }
 }
+impl UartCkPin<RemapFull<USART1>> for PA8<Input> {
+    type P = PA8<Alternate<PushPull>>;
+    fn into_alternate(self) -> Self::P {
+        self.into_mode(&mut Cr)
+    }
+}
+impl<UP: UpMode> UartCtsPin<RemapFull<USART1>> for PA11<Input<UP>> {}
+impl UartRtsPin<RemapFull<USART1>> for PA12<Input> {
+    type P = PA12<Alternate<PushPull>>;
+    fn into_alternate(self) -> Self::P {
+        self.into_mode(&mut Cr)
+    }
+}
 impl<UP: UpMode> UartRxPin<RemapFull<USART1>> for PB7<Input<UP>> {}
 impl UartTxPin<RemapFull<USART1>> for PB6<Input> {
     type P = PB6<Alternate<PushPull>>;
@@ -109,6 +137,13 @@ impl UartCkPin<RemapDefault<USART2>> for PA4<Input> {
         self.into_mode(&mut Cr)
     }
 }
+impl<UP: UpMode> UartCtsPin<RemapDefault<USART2>> for PA0<Input<UP>> {}
+impl UartRtsPin<RemapDefault<USART2>> for PA1<Input> {
+    type P = PA1<Alternate<PushPull>>;
+    fn into_alternate(self) -> Self::P {
+        self.into_mode(&mut Cr)
+    }
+}
 impl<UP: UpMode> UartRxPin<RemapDefault<USART2>> for PA3<Input<UP>> {}
 impl UartTxPin<RemapDefault<USART2>> for PA2<Input> {
     type P = PA2<Alternate<PushPull>>;
@@ -122,6 +157,13 @@ impl UartCkPin<RemapFull<USART2>> for PD7<Input> {
         self.into_mode(&mut Cr)
     }
 }
+impl<UP: UpMode> UartCtsPin<RemapFull<USART2>> for PD3<Input<UP>> {}
+impl UartRtsPin<RemapFull<USART2>> for PD4<Input> {
+    type P = PD4<Alternate<PushPull>>;
+    fn into_alternate(self) -> Self::P {
+        self.into_mode(&mut Cr)
+    }
+}
 impl<UP: UpMode> UartRxPin<RemapFull<USART2>> for PD6<Input<UP>> {}
 impl UartTxPin<RemapFull<USART2>> for PD5<Input> {
     type P = PD5<Alternate<PushPull>>;
@@ -135,6 +177,13 @@ impl UartCkPin<RemapDefault<USART3>> for PB12<Input> {
         self.into_mode(&mut Cr)
     }
 }
+impl<UP: UpMode> UartCtsPin<RemapDefault<USART3>> for PB13<Input<UP>> {}
+impl UartRtsPin<RemapDefault<USART3>> for PB14<Input> {
+    type P = PB14<Alternate<PushPull>>;
+    fn into_alternate(self) -> Self::P {
+        self.into_mode(&mut Cr)
+    }
+}
 impl<UP: UpMode> UartRxPin<RemapDefault<USART3>> for PB11<Input<UP>> {}
 impl UartTxPin<RemapDefault<USART3>> for PB10<Input> {
     type P = PB10<Alternate<PushPull>>;
@@ -148,6 +197,13 @@ impl UartCkPin<RemapFull<USART3>> for PD10<Input> {
         self.into_mode(&mut Cr)
     }
 }
+impl<UP: UpMode> UartCtsPin<RemapFull<USART3>> for PD11<Input<UP>> {}
+impl UartRtsPin<RemapFull<USART3>> for PD12<Input> {
+    type P = PD12<Alternate<PushPull>>;
+    fn into_alternate(self) -> Self::P {
+        self.into_mode(&mut Cr)
+    }
+}
 impl<UP: UpMode> UartRxPin<RemapFull<USART3>> for PD9<Input<UP>> {}
 impl UartTxPin<RemapFull<USART3>> for PD8<Input> {
     type P = PD8<Alternate<PushPull>>;
@@ -161,6 +217,13 @@ impl UartCkPin<RemapPartial1<USART3>> for PC12<Input> {
         self.into_mode(&mut Cr)
     }
 }
+impl<UP: UpMode> UartCtsPin<RemapPartial1<USART3>> for PB13<Input<UP>> {}
+impl UartRtsPin<RemapPartial1<USART3>> for PB14<Input> {
+    type P = PB14<Alternate<PushPull>>;
+    fn into_alternate(self) -> Self::P {
+        self.into_mode(&mut Cr)
+    }
+}
 impl<UP: UpMode> UartRxPin<RemapPartial1<USART3>> for PC11<Input<UP>> {}
 impl UartTxPin<RemapPartial1<USART3>> for PC10<Input> {
     type P = PC10<Alternate<PushPull>>;
@@ -0,0 +1,164 @@
+//! The CRC unit computes a non-reflected CRC-32 over 32-bit words.
+//! [`CompatCrc32`] turns it into the standard CRC-32 used by zlib, Ethernet,
+//! PNG and Python's `binascii.crc32`.
+
+pub trait CrcPeriph {
+    /// Reset the data register to `0xFFFF_FFFF`
+    fn reset(&mut self);
+    fn write(&mut self, word: u32);
+    fn get_crc(&self) -> u32;
+}
+
+/// Reflected polynomial of CRC-32
+const POLY_REFLECTED: u32 = 0xEDB8_8320;
+
+/// CRC-32 that is bit-identical to zlib's `crc32()`.
+///
+/// - Every 4 bytes are packed in little-endian order and bit-reversed before
+///   they are written to the unit, so the input is reflected.
+/// - Up to 3 bytes are kept until the next call completes a word, and they are
+///   calculated in software when the result is read.
+/// - The result is bit-reversed and inverted.
+pub struct CompatCrc32<P> {
+    crc: P,
+    tail: [u8; 4],
+    tail_len: usize,
+}
+
+impl<P: CrcPeriph> CompatCrc32<P> {
+    pub fn new(mut crc: P) -> Self {
+        crc.reset();
+        Self {
+            crc,
+            tail: [0; 4],
+            tail_len: 0,
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.crc.reset();
+        self.tail_len = 0;
+    }
+
+    /// Feed bytes and return the CRC of all data since the last reset.
+    pub fn update(&mut self, mut data: &[u8]) -> u32 {
+        if self.tail_len > 0 {
+            let n = data.len().min(4 - self.tail_len);
+            self.tail[self.tail_len..self.tail_len + n].copy_from_slice(&data[..n]);
+            self.tail_len += n;
+            data = &data[n..];
+
+            if self.tail_len < 4 {
+                return self.get_crc();
+            }
+            self.write_word(self.tail);
+            self.tail_len = 0;
+        }
+
+        let mut chunks = data.chunks_exact(4);
+        for c in &mut chunks {
+            self.write_word([c[0], c[1], c[2], c[3]]);
+        }
+
+        let rest = chunks.remainder();
+        self.tail[..rest.len()].copy_from_slice(rest);
+        self.tail_len = rest.len();
+        self.get_crc()
+    }
+
+    /// The CRC of all data since the last reset
+    pub fn get_crc(&self) -> u32 {
+        let state = self.crc.get_crc().reverse_bits();
+        !update_reflected(state, &self.tail[..self.tail_len])
+    }
+
+    pub fn release(self) -> P {
+        self.crc
+    }
+
+    #[inline(always)]
+    fn write_word(&mut self, bytes: [u8; 4]) {
+        self.crc.write(u32::from_le_bytes(bytes).reverse_bits());
+    }
+}
+
+/// Bitwise reflected CRC-32 without the final XOR.
+fn update_reflected(mut state: u32, data: &[u8]) -> u32 {
+    for &b in data {
+        state ^= b as u32;
+        for _ in 0..8 {
+            let mask = (state & 1).wrapping_neg();
+            state = (state >> 1) ^ (POLY_REFLECTED & mask);
+        }
+    }
+    state
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Software model of the CRC unit
+    struct SoftCrc(u32);
+
+    impl CrcPeriph for SoftCrc {
+        fn reset(&mut self) {
+            self.0 = 0xFFFF_FFFF;
+        }
+
+        fn write(&mut self, word: u32) {
+            self.0 ^= word;
+            for _ in 0..32 {
+                self.0 = if self.0 & 0x8000_0000 != 0 {
+                    (self.0 << 1) ^ 0x04C1_1DB7
+                } else {
+                    self.0 << 1
+                };
+            }
+        }
+
+        fn get_crc(&self) -> u32 {
+            self.0
+        }
+    }
+
+    fn crc32(data: &[u8]) -> u32 {
+        CompatCrc32::new(SoftCrc(0)).update(data)
+    }
+
+    #[test]
+    fn known_vectors() {
+        assert_eq!(crc32(b""), 0);
+        assert_eq!(crc32(b"a"), 0xE8B7_BE43);
+        assert_eq!(crc32(b"abc"), 0x3524_41C2);
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+        assert_eq!(
+            crc32(b"The quick brown fox jumps over the lazy dog"),
+            0x414F_A339
+        );
+        assert_eq!(crc32(&[0; 32]), 0x190A_55AD);
+        assert_eq!(crc32(&[0xFF; 32]), 0xFF6C_AB0B);
+    }
+
+    #[test]
+    fn streaming() {
+        let data = b"The quick brown fox jumps over the lazy dog";
+        for step in 1..9 {
+            let mut crc = CompatCrc32::new(SoftCrc(0));
+            for c in data.chunks(step) {
+                crc.update(c);
+            }
+            assert_eq!(crc.get_crc(), 0x414F_A339);
+        }
+
+        let mut crc = CompatCrc32::new(SoftCrc(0));
+        crc.update(b"12");
+        crc.update(b"");
+        crc.update(b"34567");
+        assert_eq!(crc.update(b"89"), 0xCBF4_3926);
+
+        crc.reset();
+        assert_eq!(crc.get_crc(), 0);
+        assert_eq!(crc.update(b"123456789"), 0xCBF4_3926);
+    }
+}
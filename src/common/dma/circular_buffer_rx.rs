@@ -1,9 +1,48 @@
 use super::*;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+/// What to do when the DMA has overwritten the unread data
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub enum OverrunPolicy {
+    /// Drop the oldest data and keep the newest half of the buffer.
+    #[default]
+    DropOldest,
+    /// Drop all the unread data.
+    Reset,
+}
+
+/// Counts the half laps of the DMA, [`DmaRxLaps::half`] should be called on every
+/// half transfer interrupt, and [`DmaRxLaps::complete`] on every transfer complete interrupt.
+#[derive(Clone)]
+pub struct DmaRxLaps(Arc<AtomicUsize>);
+
+impl DmaRxLaps {
+    fn new() -> Self {
+        Self(Arc::new(AtomicUsize::new(0)))
+    }
+
+    #[inline]
+    pub fn half(&self) {
+        self.0.fetch_add(1, Ordering::Release);
+    }
+
+    #[inline]
+    pub fn complete(&self) {
+        self.0.fetch_add(1, Ordering::Release);
+    }
+
+    #[inline]
+    fn get(&self) -> usize {
+        self.0.load(Ordering::Acquire)
+    }
+}
 
 /// A buffer used for DMA cyclic data reception, continuously read by the user.
 pub struct DmaCircularBufferRx<T: Sized, CH> {
     ch: CH,
     buf: CircularBuffer<T>,
+    laps: DmaRxLaps,
+    policy: OverrunPolicy,
 }
 
 impl<T, CH> DmaCircularBufferRx<T, CH>
@@ -11,13 +50,37 @@ where
     T: Sized + Copy,
     CH: DmaChannel,
 {
+    /// An odd `buf_size` is rounded up, so the half transfer is at the middle of the buffer.
     pub fn new(mut ch: CH, peripheral_addr: usize, buf_size: usize) -> Self {
-        let buf = CircularBuffer::<T>::new(buf_size);
+        let buf = CircularBuffer::<T>::new(buf_size + (buf_size & 1));
         ch.stop();
         ch.set_memory_buf_for_peripheral(buf.as_slice());
         ch.set_peripheral_address::<T>(peripheral_addr, false, false, true);
         ch.start();
-        Self { ch, buf }
+        Self {
+            ch,
+            buf,
+            laps: DmaRxLaps::new(),
+            policy: OverrunPolicy::default(),
+        }
+    }
+
+    /// The returned counter should be updated in the DMA interrupt handler,
+    /// otherwise the overrun can't be detected.
+    pub fn get_laps(&self) -> DmaRxLaps {
+        self.laps.clone()
+    }
+
+    pub fn set_overrun_policy(&mut self, policy: OverrunPolicy) {
+        self.policy = policy;
+    }
+
+    /// Returns `true` if the unread data have been overwritten by the DMA.
+    /// The read position is resynchronized according to the [`OverrunPolicy`].
+    pub fn check_overrun(&mut self) -> bool {
+        let laps = self.laps.get();
+        self.buf
+            .check_overrun(self.ch.get_unprocessed_len(), laps, self.policy)
     }
 
     #[inline]
@@ -30,6 +93,26 @@ where
         self.buf.consume(len);
     }
 
+    /// Read all the data received before the DMA remaining length was `unprocessed_len`.
+    /// The data may span the end of the buffer, so it's returned as two slices.
+    /// The slices are only valid until the DMA laps the buffer.
+    pub(crate) fn read_until(&mut self, unprocessed_len: usize) -> (&[T], &[T]) {
+        self.buf.read_until(unprocessed_len)
+    }
+
+    /// Whether any data is received before the DMA remaining length was `unprocessed_len`.
+    pub(crate) fn has_data_until(&self, unprocessed_len: usize) -> bool {
+        self.buf.read_idx != self.buf.get_recv_index(unprocessed_len)
+    }
+
+    /// Drop all the unread data.
+    pub fn discard(&mut self) {
+        let laps = self.laps.get();
+        let recv_idx = self.buf.get_recv_index(self.ch.get_unprocessed_len());
+        self.buf.read_idx = recv_idx;
+        self.buf.read_halves = self.buf.write_halves(recv_idx, laps);
+    }
+
     pub fn has_data(&self) -> bool {
         let recv_idx = self.buf.get_recv_index(self.ch.get_unprocessed_len());
         self.buf.read_idx != recv_idx
@@ -39,6 +122,8 @@ where
 pub struct CircularBuffer<T> {
     recv_buf: Vec<T>,
     read_idx: usize,
+    /// The number of half buffers that have been read, it can wrap around.
+    read_halves: usize,
 }
 
 impl<T: Sized + Copy> CircularBuffer<T> {
@@ -52,6 +137,24 @@ impl<T: Sized + Copy> CircularBuffer<T> {
         Self {
             recv_buf,
             read_idx: 0,
+            read_halves: 0,
+        }
+    }
+
+    /// The number of half buffers passed by an index, which may exceed the buffer.
+    fn halves(&self, idx: usize) -> usize {
+        let len = self.recv_buf.len();
+        idx / len * 2 + (idx % len >= len / 2) as usize
+    }
+
+    /// The number of half buffers that have been written.
+    /// `laps` is the count of the half transfer and transfer complete interrupts,
+    /// and an interrupt that is not handled yet is counted in.
+    fn write_halves(&self, recv_idx: usize, laps: usize) -> usize {
+        if laps % 2 == self.halves(recv_idx) {
+            laps
+        } else {
+            laps.wrapping_add(1)
         }
     }
 
@@ -87,13 +190,63 @@ impl<T: Sized + Copy> CircularBuffer<T> {
         Some(unsafe { core::slice::from_raw_parts(rst.as_ptr(), rst.len()) })
     }
 
+    fn read_until<'b>(&mut self, unprocessed_len: usize) -> (&'b [T], &'b [T]) {
+        let end = self.get_recv_index(unprocessed_len);
+        let (first, second) = if end >= self.read_idx {
+            (&self.recv_buf[self.read_idx..end], &self.recv_buf[..0])
+        } else {
+            (&self.recv_buf[self.read_idx..], &self.recv_buf[..end])
+        };
+        let rst = unsafe {
+            (
+                core::slice::from_raw_parts(first.as_ptr(), first.len()),
+                core::slice::from_raw_parts(second.as_ptr(), second.len()),
+            )
+        };
+        self.consume(first.len() + second.len());
+        rst
+    }
+
     fn consume(&mut self, len: usize) {
         let end = self.read_idx + len;
-        if end >= self.recv_buf.len() {
-            self.read_idx = end - self.recv_buf.len();
-        } else {
-            self.read_idx = end;
+        let passed = self.halves(end) - self.halves(self.read_idx);
+        self.read_halves = self.read_halves.wrapping_add(passed);
+        self.read_idx = end % self.recv_buf.len();
+    }
+
+    fn check_overrun(
+        &mut self,
+        unprocessed_len: usize,
+        laps: usize,
+        policy: OverrunPolicy,
+    ) -> bool {
+        let recv_idx = self.get_recv_index(unprocessed_len);
+        let write_halves = self.write_halves(recv_idx, laps);
+        let overrun = match write_halves.wrapping_sub(self.read_halves) {
+            // The writer is in the same half or the next half
+            0 | 1 => false,
+            // A full buffer is also treated as overrun, the oldest data is being overwritten.
+            2 => recv_idx >= self.read_idx,
+            // The interrupts may not be handled yet
+            n if n > usize::MAX / 2 => false,
+            _ => true,
+        };
+
+        if overrun {
+            match policy {
+                OverrunPolicy::DropOldest => {
+                    // Keep the newest half, it starts in the previous half of the buffer
+                    let len = self.recv_buf.len();
+                    self.read_idx = (recv_idx + len - len / 2) % len;
+                    self.read_halves = write_halves.wrapping_sub(1);
+                }
+                OverrunPolicy::Reset => {
+                    self.read_idx = recv_idx;
+                    self.read_halves = write_halves;
+                }
+            }
         }
+        overrun
     }
 
     // for unit test
@@ -115,6 +268,7 @@ impl<T: Sized + Copy> CircularBuffer<T> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::common::dma::mock::MockDma;
 
     #[test]
     fn circular_buffer() {
@@ -158,4 +312,126 @@ mod tests {
         assert_eq!(buf.pop_slice(10, usize::MAX), Some([0u8, 1, 2].as_slice()));
         assert_eq!(buf.pop_slice(10, usize::MAX), None);
     }
+
+    fn read_all(rx: &mut DmaCircularBufferRx<u8, MockDma>) -> Vec<u8> {
+        let mut v = Vec::new();
+        while let Some(d) = rx.read_slice(usize::MAX) {
+            v.extend_from_slice(d);
+            rx.consume(d.len());
+        }
+        v
+    }
+
+    #[test]
+    fn dma_overrun() {
+        let dma = MockDma::default();
+        let mut rx = DmaCircularBufferRx::<u8, _>::new(dma.clone(), 0, 8);
+        let laps = rx.get_laps();
+
+        // Wrap around without overrun
+        dma.receive(&[0, 1, 2, 3, 4, 5], Some(&laps));
+        assert!(!rx.check_overrun());
+        assert_eq!(read_all(&mut rx), [0, 1, 2, 3, 4, 5]);
+        dma.receive(&[6, 7, 8, 9, 10], Some(&laps));
+        assert!(!rx.check_overrun());
+        assert_eq!(read_all(&mut rx), [6, 7, 8, 9, 10]);
+
+        // Full buffer is an overrun, the newest half is kept
+        dma.receive(&[11, 12, 13, 14, 15, 16, 17, 18], Some(&laps));
+        assert!(rx.check_overrun());
+        assert!(!rx.check_overrun());
+        assert_eq!(read_all(&mut rx), [15, 16, 17, 18]);
+
+        // Lapped more than once
+        dma.receive(&(20..40).collect::<Vec<u8>>(), Some(&laps));
+        assert!(rx.check_overrun());
+        assert_eq!(read_all(&mut rx), [36, 37, 38, 39]);
+
+        // The newest half crosses the end of the buffer
+        dma.receive(&(40..50).collect::<Vec<u8>>(), Some(&laps));
+        assert!(rx.check_overrun());
+        assert_eq!(read_all(&mut rx), [46, 47, 48, 49]);
+        assert!(!rx.check_overrun());
+    }
+
+    #[test]
+    fn read_until() {
+        let dma = MockDma::default();
+        let mut rx = DmaCircularBufferRx::<u8, _>::new(dma.clone(), 0, 8);
+        let laps = rx.get_laps();
+
+        dma.receive(&[0, 1, 2], Some(&laps));
+        let end1 = dma.get_unprocessed_len();
+        dma.receive(&[3, 4, 5], Some(&laps));
+        let end2 = dma.get_unprocessed_len();
+        assert_eq!(rx.read_until(end1), ([0u8, 1, 2].as_slice(), [].as_slice()));
+        assert_eq!(rx.read_until(end2), ([3u8, 4, 5].as_slice(), [].as_slice()));
+
+        // Spans the end of the buffer
+        dma.receive(&[6, 7, 8, 9], Some(&laps));
+        let end3 = dma.get_unprocessed_len();
+        assert!(!rx.check_overrun());
+        assert_eq!(
+            rx.read_until(end3),
+            ([6u8, 7].as_slice(), [8u8, 9].as_slice())
+        );
+        assert_eq!(rx.read_until(end3), ([].as_slice(), [].as_slice()));
+    }
+
+    #[test]
+    fn dma_overrun_reset() {
+        let dma = MockDma::default();
+        let mut rx = DmaCircularBufferRx::<u8, _>::new(dma.clone(), 0, 8);
+        rx.set_overrun_policy(OverrunPolicy::Reset);
+        let laps = rx.get_laps();
+
+        dma.receive(&(0..11).collect::<Vec<u8>>(), Some(&laps));
+        assert!(rx.check_overrun());
+        assert_eq!(read_all(&mut rx), []);
+
+        dma.receive(&[11, 12, 13], Some(&laps));
+        assert!(!rx.check_overrun());
+        assert_eq!(read_all(&mut rx), [11, 12, 13]);
+    }
+
+    #[test]
+    fn dma_laps_not_handled() {
+        let dma = MockDma::default();
+        let mut rx = DmaCircularBufferRx::<u8, _>::new(dma.clone(), 0, 8);
+        let laps = rx.get_laps();
+
+        dma.receive(&[0, 1, 2, 3, 4, 5], Some(&laps));
+        assert_eq!(read_all(&mut rx), [0, 1, 2, 3, 4, 5]);
+        // The transfer complete interrupt is pending, the reader is one lap ahead
+        dma.receive(&[6, 7, 8, 9], None);
+        assert_eq!(read_all(&mut rx), [6, 7, 8, 9]);
+        assert!(!rx.check_overrun());
+        laps.complete();
+        assert!(!rx.check_overrun());
+    }
+
+    #[test]
+    fn dma_overrun_half_laps() {
+        let dma = MockDma::default();
+        let mut rx = DmaCircularBufferRx::<u8, _>::new(dma.clone(), 0, 8);
+        let laps = rx.get_laps();
+
+        dma.receive(&[0, 1], Some(&laps));
+        assert_eq!(read_all(&mut rx), [0, 1]);
+        dma.receive(&[2, 3, 4, 5], Some(&laps));
+        // The transfer complete interrupt is pending, and the read index is passed
+        dma.receive(&[6, 7, 8, 9, 10], None);
+        assert!(rx.check_overrun());
+        assert_eq!(read_all(&mut rx), [7, 8, 9, 10]);
+        laps.complete();
+        assert!(!rx.check_overrun());
+
+        // Discard with a pending transfer complete interrupt
+        dma.receive(&[11, 12, 13, 14], Some(&laps));
+        dma.receive(&[15, 16], None);
+        rx.discard();
+        laps.complete();
+        assert!(!rx.check_overrun());
+        assert_eq!(read_all(&mut rx), []);
+    }
 }
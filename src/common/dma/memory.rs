@@ -0,0 +1,189 @@
+//! Memory-to-memory transfer, the CPU is free during copying.
+//!
+//! The transfer owns the buffers like [`Transfer`], so they can't be touched
+//! until they're returned, even if the transfer is forgotten.
+
+use super::*;
+use crate::{
+    Steal,
+    common::{
+        embedded_dma::{ReadBuffer, WriteBuffer},
+        fugit::MicrosDurationU32,
+        os_trait::Duration,
+    },
+    l,
+};
+use core::{
+    mem::ManuallyDrop,
+    sync::atomic::{Ordering, compiler_fence},
+};
+
+pub struct DmaMemory<CH, OS: OsInterface> {
+    ch: CH,
+    waiter: OS::NotifyWaiter,
+}
+
+impl<CH, OS> DmaMemory<CH, OS>
+where
+    CH: DmaChannel + Steal,
+    OS: OsInterface,
+{
+    pub fn new(mut ch: CH) -> (Self, DmaMemoryNotify<CH, OS>) {
+        let (notifier, waiter) = OS::notify();
+        ch.stop();
+        ch.set_interrupt(DmaEvent::TransferComplete, true);
+        let ch2 = unsafe { ch.steal() };
+        (Self { ch, waiter }, DmaMemoryNotify { ch: ch2, notifier })
+    }
+}
+
+impl<CH, OS> DmaMemory<CH, OS>
+where
+    CH: DmaChannel,
+    OS: OsInterface,
+{
+    /// Start copying `src` to `dst`.
+    ///
+    /// - The size of `W` should be 1, 2 or 4.
+    /// - The length should be the same and not larger than 65535.
+    pub fn copy<SRC, DST, W>(
+        &mut self,
+        src: SRC,
+        mut dst: DST,
+    ) -> DmaMemoryTransfer<'_, SRC, DST, CH, OS>
+    where
+        SRC: ReadBuffer<Word = W> + 'static,
+        DST: WriteBuffer<Word = W> + 'static,
+        W: Sized + Copy,
+    {
+        let (src_ptr, src_len) = unsafe { src.read_buffer() };
+        let (dst_ptr, dst_len) = unsafe { dst.write_buffer() };
+        l::assert!(src_len == dst_len);
+        self.start::<W>(src_ptr as usize, true, dst_ptr as usize, dst_len);
+        DmaMemoryTransfer {
+            mem: self,
+            src,
+            dst,
+        }
+    }
+
+    /// Start filling `dst` with `value`.
+    ///
+    /// - The size of `W` should be 1, 2 or 4.
+    /// - The length should not be larger than 65535.
+    pub fn fill<SRC, DST, W>(
+        &mut self,
+        value: SRC,
+        mut dst: DST,
+    ) -> DmaMemoryTransfer<'_, SRC, DST, CH, OS>
+    where
+        SRC: ReadBuffer<Word = W> + 'static,
+        DST: WriteBuffer<Word = W> + 'static,
+        W: Sized + Copy,
+    {
+        let (src_ptr, src_len) = unsafe { value.read_buffer() };
+        let (dst_ptr, dst_len) = unsafe { dst.write_buffer() };
+        l::assert!(src_len >= 1);
+        self.start::<W>(src_ptr as usize, false, dst_ptr as usize, dst_len);
+        DmaMemoryTransfer {
+            mem: self,
+            src: value,
+            dst,
+        }
+    }
+
+    fn start<W: Sized + Copy>(
+        &mut self,
+        src_addr: usize,
+        src_increase: bool,
+        dst_addr: usize,
+        len: usize,
+    ) {
+        l::assert!(matches!(core::mem::size_of::<W>(), 1 | 2 | 4));
+        self.ch.stop();
+        if len > 0 {
+            self.ch
+                .set_memory_to_memory::<W>(src_addr, src_increase, dst_addr, len);
+            // Make sure the source is written before the DMA reads it.
+            compiler_fence(Ordering::Release);
+            self.ch.start();
+        }
+    }
+}
+
+/// The transfer is stopped when it's dropped.
+pub struct DmaMemoryTransfer<'a, SRC, DST, CH: DmaChannel, OS: OsInterface> {
+    mem: &'a mut DmaMemory<CH, OS>,
+    src: SRC,
+    dst: DST,
+}
+
+impl<SRC, DST, CH, OS> DmaMemoryTransfer<'_, SRC, DST, CH, OS>
+where
+    CH: DmaChannel,
+    OS: OsInterface,
+{
+    #[inline]
+    pub fn is_done(&self) -> bool {
+        !self.mem.ch.in_progress()
+    }
+
+    /// Wait until the transfer is done and get the buffers back.
+    /// Returns itself on timeout, it can be waited again or aborted.
+    pub fn wait(self, timeout: MicrosDurationU32) -> Result<(SRC, DST), Self> {
+        let done = self
+            .mem
+            .waiter
+            .wait_with(&Duration::<OS>::micros(timeout.ticks()), || {
+                (!self.mem.ch.in_progress()).then_some(())
+            });
+        match done {
+            Some(()) => Ok(self.into_parts()),
+            None => Err(self),
+        }
+    }
+
+    /// Stop the transfer even if it's in progress, and get the buffers back.
+    pub fn abort(self) -> (SRC, DST) {
+        self.into_parts()
+    }
+
+    fn into_parts(self) -> (SRC, DST) {
+        let mut this = ManuallyDrop::new(self);
+        this.mem.ch.stop();
+        // Make sure the buffer is read after the DMA has written it.
+        compiler_fence(Ordering::Acquire);
+        unsafe { (core::ptr::read(&this.src), core::ptr::read(&this.dst)) }
+    }
+}
+
+impl<SRC, DST, CH, OS> Drop for DmaMemoryTransfer<'_, SRC, DST, CH, OS>
+where
+    CH: DmaChannel,
+    OS: OsInterface,
+{
+    fn drop(&mut self) {
+        self.mem.ch.stop();
+    }
+}
+
+/// Call `interrupt_notify` in the DMA channel interrupt callback.
+pub struct DmaMemoryNotify<CH, OS: OsInterface> {
+    ch: CH,
+    notifier: OS::Notifier,
+}
+
+impl<CH, OS> DmaMemoryNotify<CH, OS>
+where
+    CH: DmaChannel,
+    OS: OsInterface,
+{
+    pub fn interrupt_notify(&mut self) {
+        if self
+            .ch
+            .check_and_clear_interrupt(DmaEvent::TransferComplete)
+        {
+            self.notifier.notify();
+        }
+    }
+}
@@ -0,0 +1,102 @@
+//! A channel for the host tests, the transfers are simulated by [`MockDma::receive`].
+
+use super::*;
+use crate::Steal;
+use core::cell::RefCell;
+
+#[derive(Default)]
+pub(crate) struct MockDmaState {
+    addr: usize,
+    len: usize,
+    pos: usize,
+    enabled: bool,
+    circular: bool,
+}
+
+/// Simulates a peripheral-to-memory channel
+#[derive(Clone, Default)]
+pub(crate) struct MockDma(Arc<RefCell<MockDmaState>>);
+
+impl MockDma {
+    /// Write `data` into the memory. A circular transfer wraps around and completes a lap
+    /// at the end of the buffer, otherwise the rest of the data are dropped.
+    /// The interrupts are counted in `laps` if it's given.
+    pub(crate) fn receive(&self, data: &[u8], laps: Option<&DmaRxLaps>) {
+        let mut s = self.0.borrow_mut();
+        for &d in data {
+            if !s.circular && s.pos == s.len {
+                break;
+            }
+            unsafe { *(s.addr as *mut u8).add(s.pos) = d };
+            s.pos += 1;
+            match laps {
+                Some(laps) if s.pos == s.len / 2 => laps.half(),
+                Some(laps) if s.pos == s.len => laps.complete(),
+                _ => (),
+            }
+            if s.circular && s.pos == s.len {
+                s.pos = 0;
+            }
+        }
+    }
+
+    pub(crate) fn is_enabled(&self) -> bool {
+        self.0.borrow().enabled
+    }
+}
+
+impl Steal for MockDma {
+    unsafe fn steal(&self) -> Self {
+        self.clone()
+    }
+}
+
+impl DmaChannel for MockDma {
+    fn start(&mut self) {
+        self.0.borrow_mut().enabled = true;
+    }
+    /// The transfer length is cleared, same as the hardware.
+    fn stop(&mut self) {
+        let mut s = self.0.borrow_mut();
+        s.enabled = false;
+        s.len = 0;
+        s.pos = 0;
+    }
+    fn set_peripheral_address<T: Sized + Copy>(
+        &mut self,
+        _address: usize,
+        _mem_to_periph: bool,
+        _increase: bool,
+        circular: bool,
+    ) {
+        self.0.borrow_mut().circular = circular;
+    }
+    fn set_memory_address(&mut self, address: usize, _increase: bool) {
+        self.0.borrow_mut().addr = address;
+    }
+    fn set_transfer_length(&mut self, len: usize) {
+        let mut s = self.0.borrow_mut();
+        s.len = len;
+        s.pos = 0;
+    }
+    fn set_memory_to_memory<T: Sized + Copy>(
+        &mut self,
+        _src: usize,
+        _src_increase: bool,
+        _dst: usize,
+        _len: usize,
+    ) {
+    }
+    fn get_unprocessed_len(&self) -> usize {
+        let s = self.0.borrow();
+        s.len - s.pos
+    }
+    fn in_progress(&self) -> bool {
+        let s = self.0.borrow();
+        s.enabled && s.pos != s.len
+    }
+    fn set_interrupt(&mut self, _event: DmaEvent, _enable: bool) {}
+    fn check_and_clear_interrupt(&mut self, _event: DmaEvent) -> bool {
+        false
+    }
+}
@@ -1,8 +1,16 @@
 mod circular_buffer_rx;
+mod memory;
+#[cfg(test)]
+pub(crate) mod mock;
 mod ringbuf_tx;
+mod shared;
+mod transfer;
 
 pub use circular_buffer_rx::*;
+pub use memory::*;
 pub use ringbuf_tx::*;
+pub use shared::*;
+pub use transfer::*;
 
 use crate::common::prelude::*;
 
@@ -24,9 +32,12 @@ pub trait DmaChannel {
         self.set_transfer_length(buf.len());
     }
 
+    /// Configure a transfer from `src_addr` to `dst_addr` without peripheral requests.
+    /// The destination address is always increased. `len` is the number of `T`.
     fn set_memory_to_memory<T: Sized + Copy>(
         &mut self,
         src_addr: usize,
+        src_increase: bool,
         dst_addr: usize,
         len: usize,
     );
@@ -1,5 +1,6 @@
 use super::*;
 use crate::{
+    Steal,
     common::{critical_section::Mutex, ringbuf::*},
     l,
 };
@@ -76,6 +77,19 @@ where
         });
     }
 
+    /// Get another instance of the channel to transfer other data directly.
+    /// Call [`DmaRingbufTxWriter::cancel`] first, the ring buffer is resumed by the next write.
+    ///
+    /// # Safety
+    ///
+    /// The ring buffer must not be written until the instance is stopped.
+    pub unsafe fn steal_channel(&self) -> CH
+    where
+        CH: Steal,
+    {
+        critical_section::with(|cs| unsafe { self.dma.borrow_ref(cs).ch.steal() })
+    }
+
     #[inline]
     fn reload(&mut self) {
         critical_section::with(|cs| {
@@ -108,6 +122,11 @@ where
         });
     }
 
+    #[inline]
+    pub fn in_progress(&self) -> bool {
+        critical_section::with(|cs| self.dma.borrow_ref(cs).in_progress())
+    }
+
     pub fn interrupt_reload(&mut self) {
         let reloaded = critical_section::with(|cs| {
             let mut dma = self.dma.borrow_ref_mut(cs);
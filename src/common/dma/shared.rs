@@ -0,0 +1,234 @@
+//! Lend one DMA channel to different peripherals over time.
+//!
+//! Some channels are bound to several peripherals, for example `dma1::C4` is
+//! bound to USART1 TX, SPI2 RX and I2C2 TX. Put such a channel into a
+//! [`DmaSharedChannel`] and borrow a [`DmaChannelLease`] whenever a peripheral
+//! needs it. The channel is stopped and returned when the lease is dropped.
+//!
+//! Every lease has a generation. The copies stolen for the interrupt handlers
+//! do nothing after their lease is returned, so they can't drive the channel
+//! while it's lent to another peripheral.
+
+use super::*;
+use crate::{Steal, common::critical_section::Mutex};
+use core::{
+    cell::RefCell,
+    mem::ManuallyDrop,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+struct SharedState<CH> {
+    ch: Mutex<RefCell<Option<CH>>>,
+    /// It's increased whenever the channel is lent or returned.
+    generation: AtomicUsize,
+}
+
+pub struct DmaSharedChannel<CH> {
+    ch: Arc<SharedState<CH>>,
+}
+
+impl<CH> Clone for DmaSharedChannel<CH> {
+    fn clone(&self) -> Self {
+        Self {
+            ch: Arc::clone(&self.ch),
+        }
+    }
+}
+
+impl<CH: DmaChannel> DmaSharedChannel<CH> {
+    pub fn new(ch: CH) -> Self {
+        Self {
+            ch: Arc::new(SharedState {
+                ch: Mutex::new(RefCell::new(Some(ch))),
+                generation: AtomicUsize::new(0),
+            }),
+        }
+    }
+
+    /// Borrow the channel, return `None` if it's lent to others.
+    pub fn try_lend(&self) -> Option<DmaChannelLease<CH>> {
+        let (ch, generation) = critical_section::with(|cs| {
+            let ch = self.ch.ch.borrow_ref_mut(cs).take()?;
+            Some((ch, self.ch.generation.fetch_add(1, Ordering::AcqRel) + 1))
+        })?;
+        Some(DmaChannelLease {
+            ch: ManuallyDrop::new(ch),
+            shared: Arc::clone(&self.ch),
+            generation,
+            owner: true,
+        })
+    }
+
+    #[inline]
+    pub fn is_available(&self) -> bool {
+        critical_section::with(|cs| self.ch.ch.borrow_ref(cs).is_some())
+    }
+}
+
+/// A borrowed channel. It can be used wherever a channel is required.
+pub struct DmaChannelLease<CH: DmaChannel> {
+    ch: ManuallyDrop<CH>,
+    shared: Arc<SharedState<CH>>,
+    generation: usize,
+    /// `false` for the stolen copies
+    owner: bool,
+}
+
+impl<CH: DmaChannel> DmaChannelLease<CH> {
+    /// `false` after the lease is returned, then all the operations are ignored.
+    /// It's always `true` for the lease itself, only the stolen copies can outlive it.
+    #[inline]
+    pub fn is_valid(&self) -> bool {
+        self.shared.generation.load(Ordering::Acquire) == self.generation
+    }
+}
+
+impl<CH: DmaChannel> Drop for DmaChannelLease<CH> {
+    fn drop(&mut self) {
+        if self.owner {
+            self.ch.stop();
+            self.ch.set_interrupt(DmaEvent::HalfTransfer, false);
+            self.ch.set_interrupt(DmaEvent::TransferComplete, false);
+            let ch = unsafe { ManuallyDrop::take(&mut self.ch) };
+            critical_section::with(|cs| {
+                // Invalidate the stolen copies before the channel can be lent again.
+                self.shared.generation.fetch_add(1, Ordering::AcqRel);
+                self.shared.ch.borrow_ref_mut(cs).replace(ch)
+            });
+        } else {
+            unsafe { ManuallyDrop::drop(&mut self.ch) };
+        }
+    }
+}
+
+/// The stolen lease never stops or returns the channel,
+/// and it does nothing after the lease is returned.
+impl<CH> Steal for DmaChannelLease<CH>
+where
+    CH: DmaChannel + Steal,
+{
+    unsafe fn steal(&self) -> Self {
+        Self {
+            ch: ManuallyDrop::new(unsafe { self.ch.steal() }),
+            shared: Arc::clone(&self.shared),
+            generation: self.generation,
+            owner: false,
+        }
+    }
+}
+
+impl<CH: DmaChannel> DmaChannel for DmaChannelLease<CH> {
+    #[inline]
+    fn start(&mut self) {
+        if self.is_valid() {
+            self.ch.start();
+        }
+    }
+
+    #[inline]
+    fn stop(&mut self) {
+        if self.is_valid() {
+            self.ch.stop();
+        }
+    }
+
+    #[inline]
+    fn set_peripheral_address<T: Sized + Copy>(
+        &mut self,
+        address: usize,
+        mem_to_periph: bool,
+        increase: bool,
+        circular: bool,
+    ) {
+        if self.is_valid() {
+            self.ch
+                .set_peripheral_address::<T>(address, mem_to_periph, increase, circular);
+        }
+    }
+
+    #[inline]
+    fn set_memory_address(&mut self, address: usize, increase: bool) {
+        if self.is_valid() {
+            self.ch.set_memory_address(address, increase);
+        }
+    }
+
+    #[inline]
+    fn set_transfer_length(&mut self, len: usize) {
+        if self.is_valid() {
+            self.ch.set_transfer_length(len);
+        }
+    }
+
+    #[inline]
+    fn set_memory_to_memory<T: Sized + Copy>(
+        &mut self,
+        src_addr: usize,
+        src_increase: bool,
+        dst_addr: usize,
+        len: usize,
+    ) {
+        if self.is_valid() {
+            self.ch
+                .set_memory_to_memory::<T>(src_addr, src_increase, dst_addr, len);
+        }
+    }
+
+    #[inline]
+    fn get_unprocessed_len(&self) -> usize {
+        if self.is_valid() {
+            self.ch.get_unprocessed_len()
+        } else {
+            0
+        }
+    }
+
+    #[inline]
+    fn in_progress(&self) -> bool {
+        self.is_valid() && self.ch.in_progress()
+    }
+
+    #[inline]
+    fn set_interrupt(&mut self, event: DmaEvent, enable: bool) {
+        if self.is_valid() {
+            self.ch.set_interrupt(event, enable);
+        }
+    }
+
+    #[inline]
+    fn check_and_clear_interrupt(&mut self, event: DmaEvent) -> bool {
+        self.is_valid() && self.ch.check_and_clear_interrupt(event)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::dma::mock::MockDma;
+
+    #[test]
+    fn stolen_lease() {
+        let dma = MockDma::default();
+        let shared = DmaSharedChannel::new(dma.clone());
+
+        let lease = shared.try_lend().unwrap();
+        let mut isr = unsafe { lease.steal() };
+        assert!(shared.try_lend().is_none());
+        isr.start();
+        assert!(dma.is_enabled());
+        drop(lease);
+        assert!(!dma.is_enabled());
+        assert!(shared.is_available());
+
+        // The stolen copy can't drive the channel lent to another peripheral.
+        let mut lease2 = shared.try_lend().unwrap();
+        assert!(!isr.is_valid());
+        isr.start();
+        assert!(!dma.is_enabled());
+        lease2.start();
+        assert!(dma.is_enabled());
+        assert!(!isr.in_progress());
+        drop(isr);
+        assert!(dma.is_enabled());
+    }
+}
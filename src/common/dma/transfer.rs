@@ -0,0 +1,111 @@
+//! One-shot transfer between memory and peripheral.
+//!
+//! [`Transfer`] owns the buffer and the channel while the DMA is running, so the
+//! buffer can't be dropped or touched until it's returned.
+
+use super::*;
+use crate::common::embedded_dma::{ReadBuffer, WriteBuffer};
+use core::{
+    mem::ManuallyDrop,
+    sync::atomic::{Ordering, compiler_fence},
+};
+
+/// The transfer is aborted when it's dropped.
+pub struct Transfer<BUF, CH: DmaChannel> {
+    buf: BUF,
+    ch: CH,
+}
+
+impl<BUF, CH> Transfer<BUF, CH>
+where
+    CH: DmaChannel,
+{
+    /// Start a transfer from the buffer to the peripheral.
+    /// The length of the buffer should not be larger than 65535.
+    pub fn write<W>(mut ch: CH, peripheral_addr: usize, buf: BUF) -> Self
+    where
+        BUF: ReadBuffer<Word = W>,
+        W: Sized + Copy,
+    {
+        let (ptr, len) = unsafe { buf.read_buffer() };
+        ch.stop();
+        ch.set_peripheral_address::<W>(peripheral_addr, true, false, false);
+        Self::start(ch, buf, ptr as usize, len)
+    }
+
+    /// Start a transfer from the peripheral to the buffer.
+    /// The length of the buffer should not be larger than 65535.
+    pub fn read<W>(mut ch: CH, peripheral_addr: usize, mut buf: BUF) -> Self
+    where
+        BUF: WriteBuffer<Word = W>,
+        W: Sized + Copy,
+    {
+        let (ptr, len) = unsafe { buf.write_buffer() };
+        ch.stop();
+        ch.set_peripheral_address::<W>(peripheral_addr, false, false, false);
+        Self::start(ch, buf, ptr as usize, len)
+    }
+
+    fn start(mut ch: CH, buf: BUF, addr: usize, len: usize) -> Self {
+        ch.set_memory_address(addr, true);
+        ch.set_transfer_length(len);
+        // Make sure the buffer is written before the DMA reads it.
+        compiler_fence(Ordering::Release);
+        if len > 0 {
+            ch.start();
+        }
+        Self { buf, ch }
+    }
+
+    /// The number of words that haven't been transferred
+    #[inline]
+    pub fn get_unprocessed_len(&self) -> usize {
+        if self.is_done() {
+            0
+        } else {
+            self.ch.get_unprocessed_len()
+        }
+    }
+
+    #[inline]
+    pub fn is_done(&self) -> bool {
+        !self.ch.in_progress()
+    }
+
+    /// Get the buffer and the channel back if the transfer is done.
+    pub fn release(self) -> Result<(BUF, CH), Self> {
+        if self.is_done() {
+            Ok(self.into_parts())
+        } else {
+            Err(self)
+        }
+    }
+
+    /// Block until the transfer is done.
+    pub fn wait(self) -> (BUF, CH) {
+        while !self.is_done() {}
+        self.into_parts()
+    }
+
+    /// Stop the transfer even if it's in progress.
+    pub fn abort(self) -> (BUF, CH) {
+        self.into_parts()
+    }
+
+    fn into_parts(self) -> (BUF, CH) {
+        let mut this = ManuallyDrop::new(self);
+        this.ch.stop();
+        // Make sure the buffer is read after the DMA has written it.
+        compiler_fence(Ordering::Acquire);
+        unsafe { (core::ptr::read(&this.buf), core::ptr::read(&this.ch)) }
+    }
+}
+
+impl<BUF, CH> Drop for Transfer<BUF, CH>
+where
+    CH: DmaChannel,
+{
+    fn drop(&mut self) {
+        self.ch.stop();
+    }
+}
@@ -1,5 +1,6 @@
 pub mod atomic_cell;
 pub mod atomic_mutex;
+pub mod crc;
 pub mod dma;
 pub mod holder;
 pub mod i2c;
@@ -11,6 +12,7 @@ pub mod uart;
 pub mod wrap_trait;
 
 pub use critical_section;
+pub use embedded_dma;
 pub use embedded_hal;
 pub use embedded_hal_nb;
 pub use embedded_io;
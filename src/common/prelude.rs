@@ -1,4 +1,5 @@
 pub use super::{
+    crc::CrcPeriph as _,
     dma::DmaChannel as _,
     fugit::{ExtU32 as _, RateExtU32 as _},
     i2c::I2cPeriph as _,
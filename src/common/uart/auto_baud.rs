@@ -0,0 +1,260 @@
+//! Automatic baud rate detection.
+//!
+//! The host sends a sync byte first, and the RX pin is used as a GPIO before the UART is enabled.
+//! - [`measure_baudrate`] busy-polls the pin, interrupts should be disabled while it's running.
+//! - [`AutoBaudCapture`] takes a timestamp in the falling edge interrupt (EXTI) of the pin.
+//!
+//! For both `0x55` and `0x7F`, the first falling edge is the beginning of the start bit and the last
+//! falling edge is the beginning of bit 7, so the time between them is 8 bit durations.
+
+use super::*;
+use crate::common::{
+    KilohertzU32,
+    critical_section::Mutex,
+    embedded_hal::digital::InputPin,
+    os_trait::{TickDuration, TickInstant},
+};
+use core::cell::RefCell;
+
+/// The sync byte sent by the host
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum AutoBaudSync {
+    /// `0x55`, there are falling edges at the start bit, bit 1, 3, 5 and 7.
+    Sync55,
+    /// `0x7F`, there are falling edges at the start bit and bit 7.
+    Sync7F,
+}
+
+impl AutoBaudSync {
+    #[inline]
+    pub const fn falling_edges(&self) -> usize {
+        match self {
+            Self::Sync55 => 5,
+            Self::Sync7F => 2,
+        }
+    }
+}
+
+/// `ticks`: the duration of 8 bits.
+pub fn calculate_baudrate(ticks: u64, frequency: KilohertzU32) -> u32 {
+    if ticks == 0 {
+        return 0;
+    }
+    let freq = frequency.to_Hz() as u64 * 8;
+    ((freq + ticks / 2) / ticks) as u32
+}
+
+/// Measure the sync byte on the RX pin. Returns `None` on timeout.
+///
+/// It's a busy loop, `T` should have a high resolution, such as `DwtInstant`.
+pub fn measure_baudrate<T: TickInstant>(
+    pin: &mut impl InputPin,
+    sync: AutoBaudSync,
+    timeout: MicrosDurationU32,
+) -> Option<u32> {
+    let timeout = TickDuration::<T>::micros(timeout.to_micros());
+    let mut t = T::now();
+    let mut start = T::now();
+    let mut ticks = 0;
+
+    for i in 0..sync.falling_edges() {
+        // Wait for the line to be high, it also skips a frame that is being received
+        while pin.is_low().ok()? {
+            if t.timeout(&timeout) {
+                return None;
+            }
+        }
+
+        while pin.is_high().ok()? {
+            if t.timeout(&timeout) {
+                return None;
+            }
+        }
+
+        if i == 0 {
+            start = T::now();
+        } else {
+            ticks = start.elapsed().as_ticks();
+        }
+    }
+    Some(calculate_baudrate(ticks, T::frequency()))
+}
+
+// Interrupt ------------------------------------------------------------------
+
+struct CaptureState<T> {
+    start: Option<T>,
+    edges: usize,
+    last: u64,
+    ticks: Option<u64>,
+}
+
+impl<T: TickInstant> CaptureState<T> {
+    fn restart(&mut self) {
+        self.start = Some(T::now());
+        self.edges = 1;
+        self.last = 0;
+    }
+}
+
+/// Measure the sync byte with the falling edge interrupt of the RX pin.
+///
+/// Configure the EXTI of the RX pin on the falling edge and call
+/// [`AutoBaudEdgeHandler::on_falling_edge`] in the interrupt callback.
+/// `T` should have a high resolution, such as `DwtInstant`.
+///
+/// It only measures the baud rate, the UART is not configured. Apply it with
+/// [`Uart::apply_captured_baudrate`](crate::uart::Uart::apply_captured_baudrate)
+/// before the UART is enabled, or with `UartControl::reconfigure` afterwards.
+pub struct AutoBaudCapture<T> {
+    state: Arc<Mutex<RefCell<CaptureState<T>>>>,
+}
+
+impl<T: TickInstant> AutoBaudCapture<T> {
+    /// `max_gap`: the longest time between two falling edges of the sync byte,
+    /// e.g. 2 bit durations at the lowest baud rate for `0x55`.
+    /// An edge after a longer gap restarts the measurement.
+    pub fn new(sync: AutoBaudSync, max_gap: MicrosDurationU32) -> (Self, AutoBaudEdgeHandler<T>) {
+        let state = Arc::new(Mutex::new(RefCell::new(CaptureState {
+            start: None,
+            edges: 0,
+            last: 0,
+            ticks: None,
+        })));
+        (
+            Self {
+                state: Arc::clone(&state),
+            },
+            AutoBaudEdgeHandler {
+                state,
+                edges: sync.falling_edges(),
+                max_gap: TickDuration::<T>::micros(max_gap.to_micros()).as_ticks(),
+            },
+        )
+    }
+
+    /// Returns `None` if the sync byte is not measured yet, or the edges are too close to measure.
+    pub fn baudrate(&self) -> Option<u32> {
+        let ticks = critical_section::with(|cs| self.state.borrow_ref(cs).ticks)?;
+        match calculate_baudrate(ticks, T::frequency()) {
+            0 => None,
+            baudrate => Some(baudrate),
+        }
+    }
+
+    /// Discard the measurement and wait for a new sync byte.
+    pub fn reset(&mut self) {
+        critical_section::with(|cs| {
+            let mut s = self.state.borrow_ref_mut(cs);
+            s.start = None;
+            s.edges = 0;
+            s.ticks = None;
+        });
+    }
+}
+
+pub struct AutoBaudEdgeHandler<T> {
+    state: Arc<Mutex<RefCell<CaptureState<T>>>>,
+    edges: usize,
+    max_gap: u64,
+}
+
+impl<T: TickInstant> AutoBaudEdgeHandler<T> {
+    /// Call it in the EXTI interrupt callback of the RX pin.
+    pub fn on_falling_edge(&mut self) {
+        critical_section::with(|cs| {
+            let mut s = self.state.borrow_ref_mut(cs);
+            if s.ticks.is_some() {
+                return;
+            }
+
+            let Some(now) = s.start.as_mut().map(|t| t.elapsed().as_ticks()) else {
+                s.restart();
+                return;
+            };
+            // It was an edge in the middle of a frame, or the rest of the sync byte was lost
+            if now - s.last > self.max_gap {
+                s.restart();
+                return;
+            }
+
+            s.last = now;
+            s.edges += 1;
+            if s.edges == self.edges {
+                s.ticks = Some(now);
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::fugit::{ExtU32, RateExtU32};
+    use core::sync::atomic::{AtomicU64, Ordering};
+
+    #[test]
+    fn baudrate() {
+        // 72 MHz, 115200 baud: 625 ticks per bit
+        assert_eq!(calculate_baudrate(625 * 8, 72.MHz()), 115200);
+        assert_eq!(calculate_baudrate(620 * 8, 72.MHz()), 116129);
+        assert_eq!(calculate_baudrate(7500 * 8, 72.MHz()), 9600);
+        assert_eq!(calculate_baudrate(0, 72.MHz()), 0);
+    }
+
+    static TICKS: AtomicU64 = AtomicU64::new(0);
+
+    #[derive(Clone)]
+    struct MockInstant(u64);
+
+    impl TickInstant for MockInstant {
+        fn frequency() -> KilohertzU32 {
+            72.MHz()
+        }
+
+        fn now() -> Self {
+            Self(TICKS.load(Ordering::Relaxed))
+        }
+
+        fn elapsed(&mut self) -> TickDuration<Self> {
+            TickDuration::from_ticks(Self::now().0 - self.0)
+        }
+
+        fn move_forward(&mut self, dur: &TickDuration<Self>) {
+            self.0 += dur.as_ticks();
+        }
+    }
+
+    #[test]
+    fn capture() {
+        let edge = |h: &mut AutoBaudEdgeHandler<MockInstant>, ticks| {
+            TICKS.fetch_add(ticks, Ordering::Relaxed);
+            h.on_falling_edge();
+        };
+
+        // 9600 baud at most
+        let (mut c, mut h) =
+            AutoBaudCapture::<MockInstant>::new(AutoBaudSync::Sync55, 209.micros());
+        // An edge of the previous frame
+        edge(&mut h, 0);
+        assert_eq!(c.baudrate(), None);
+        edge(&mut h, 100_000);
+        for _ in 0..3 {
+            edge(&mut h, 625 * 2);
+            assert_eq!(c.baudrate(), None);
+        }
+        edge(&mut h, 625 * 2);
+        assert_eq!(c.baudrate(), Some(115200));
+        // Ignored until reset
+        edge(&mut h, 625 * 2);
+        assert_eq!(c.baudrate(), Some(115200));
+
+        c.reset();
+        assert_eq!(c.baudrate(), None);
+        edge(&mut h, 100_000);
+        for _ in 0..4 {
+            edge(&mut h, 7500 * 2);
+        }
+        assert_eq!(c.baudrate(), Some(9600));
+    }
+}
@@ -0,0 +1,143 @@
+//! One UART interrupt shared by the transmitter and the receiver.
+//!
+//! The status is read once at the beginning of the interrupt and passed to the attached handlers,
+//! so any mix of poll, interrupt and DMA modes can share a callback. The handlers don't clear
+//! the idle and LIN break flags, they're cleared by the dispatcher after all handlers are called.
+
+use super::*;
+
+/// The pending events of a UART
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct UartStatus {
+    pub rx_not_empty: bool,
+    pub tx_empty: bool,
+    pub tx_complete: bool,
+    pub idle: bool,
+    pub lin_break: bool,
+    /// Unlike the other events, it's not masked by the interrupt enable bits.
+    pub rx_error: Option<Error>,
+}
+
+/// A handler that can be attached to [`UartInterruptDispatcher`].
+pub trait UartEventHandler {
+    /// It must not clear the idle and LIN break flags.
+    fn on_event(&mut self, status: &UartStatus);
+}
+
+/// Nothing is attached, for the poll and DMA transmitters.
+impl UartEventHandler for () {
+    #[inline]
+    fn on_event(&mut self, _status: &UartStatus) {}
+}
+
+pub struct UartInterruptDispatcher<U, TX = (), RX = ()> {
+    uart: U,
+    tx: TX,
+    rx: RX,
+}
+
+impl<U: UartPeriph> UartInterruptDispatcher<U> {
+    pub fn new(uart: U) -> Self {
+        Self {
+            uart,
+            tx: (),
+            rx: (),
+        }
+    }
+}
+
+impl<U, TX, RX> UartInterruptDispatcher<U, TX, RX>
+where
+    U: UartPeriph,
+    TX: UartEventHandler,
+    RX: UartEventHandler,
+{
+    /// Attach the handler of the transmitter, it replaces the previous one.
+    pub fn with_tx<H: UartEventHandler>(self, tx: H) -> UartInterruptDispatcher<U, H, RX> {
+        UartInterruptDispatcher {
+            uart: self.uart,
+            tx,
+            rx: self.rx,
+        }
+    }
+
+    /// Attach the handler of the receiver, it replaces the previous one.
+    pub fn with_rx<H: UartEventHandler>(self, rx: H) -> UartInterruptDispatcher<U, TX, H> {
+        UartInterruptDispatcher {
+            uart: self.uart,
+            tx: self.tx,
+            rx,
+        }
+    }
+
+    /// Call it in the UART interrupt callback.
+    pub fn handler(&mut self) {
+        let status = self.uart.get_status();
+        self.rx.on_event(&status);
+        self.tx.on_event(&status);
+
+        if status.lin_break {
+            self.uart.check_and_clear_interrupt(Event::LinBreak);
+        }
+        // It reads the data register if the receiver didn't read it.
+        if status.idle {
+            self.uart.check_and_clear_interrupt(Event::Idle);
+        }
+    }
+
+    pub fn release(self) -> (TX, RX) {
+        (self.tx, self.rx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::{
+        embedded_io::{Read, Write},
+        os_trait::FakeOs,
+        uart::mock::MockUart,
+    };
+
+    #[test]
+    fn dispatch() {
+        let mut u = MockUart::default();
+        let (mut tx, tx_it) =
+            UartInterruptTx::<_, FakeOs>::new([u.clone(), u.clone()], 8, 115200, 0.micros());
+        let (mut rx, rx_it) =
+            UartInterruptRx::<_, FakeOs>::new([u.clone(), u.clone()], 8, 0.micros());
+        u.set_interrupt(Event::Idle, true);
+        let mut d = UartInterruptDispatcher::new(u.clone())
+            .with_tx(tx_it)
+            .with_rx(rx_it);
+
+        // Both handlers are called in one interrupt
+        assert_eq!(tx.write(&[1, 2]), Ok(2));
+        u.receive(0x55, None);
+        d.handler();
+        assert_eq!(u.0.borrow().tx, [1]);
+        let mut buf = [0; 4];
+        assert_eq!(rx.read(&mut buf), Ok(1));
+        assert_eq!(buf[0], 0x55);
+
+        // The idle flag is cleared by the dispatcher
+        u.0.borrow_mut().idle = true;
+        d.handler();
+        assert!(!u.0.borrow().idle);
+        assert_eq!(u.0.borrow().tx, [1, 2]);
+        d.handler();
+        assert!(!u.is_interrupt_enable(Event::TxEmpty));
+
+        // A stale error is ignored while the RX interrupt is disabled
+        u.set_interrupt(Event::RxNotEmpty, false);
+        u.0.borrow_mut().rx_error = Some(Error::Overrun);
+        let reads = u.0.borrow().dr_reads;
+        d.handler();
+        assert_eq!(u.0.borrow().dr_reads, reads);
+
+        u.set_interrupt(Event::RxNotEmpty, true);
+        d.handler();
+        assert_eq!(u.0.borrow().rx_error, None);
+        assert_eq!(rx.read(&mut buf), Err(Error::Overrun));
+    }
+}
@@ -0,0 +1,433 @@
+//! DMX512 transmitter and receiver.
+//!
+//! A packet is a break, a mark after break (MAB), a start code and up to 512 slots,
+//! sent at 250 kbaud with 8N2.
+//!
+//! The transmitter sends the break as `0x00` at a lower baud rate: the start bit and 8 data bits
+//! are the break, and the 2 stop bits are the MAB.
+//!
+//! The receiver stores the slots by DMA, a break is detected as a framing error and
+//! completes the packet being received.
+
+use super::*;
+use crate::common::{dma::DmaChannel, embedded_io::Write, os_trait::Timeout};
+use core::{
+    cell::UnsafeCell,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+pub const DMX_BAUDRATE: u32 = 250_000;
+/// 9 low bits are 90 us of break, and 2 stop bits are 20 us of MAB.
+const DMX_BREAK_BAUDRATE: u32 = 100_000;
+pub const DMX_SLOTS: usize = 512;
+
+impl Config {
+    /// 250 kbaud, 8 data bits, no parity and 2 stop bits
+    pub fn dmx512() -> Self {
+        Self {
+            baudrate: DMX_BAUDRATE,
+            word_length: WordLength::Bits8,
+            parity: Parity::ParityNone,
+            stop_bits: StopBits::STOP2,
+        }
+    }
+}
+
+// TX -------------------------------------------------------------------------
+
+pub struct DmxTx<U, CH, OS: OsInterface> {
+    tx: UartDmaBufTx<U, CH, OS>,
+    uart: U,
+    break_timeout: MicrosDurationU32,
+}
+
+impl<U, CH, OS> DmxTx<U, CH, OS>
+where
+    U: UartPeriph,
+    CH: DmaChannel,
+    OS: OsInterface,
+{
+    /// The UART should be configured with [`Config::dmx512`].
+    /// `uart` is used to switch the baud rate. The buffer of `tx` should hold a whole packet.
+    pub fn new(tx: UartDmaBufTx<U, CH, OS>, uart: U) -> Self {
+        Self {
+            tx,
+            uart,
+            break_timeout: calculate_timeout(DMX_BREAK_BAUDRATE, 4),
+        }
+    }
+
+    /// Send a packet after the previous one is complete. `slots` can't be longer than 512.
+    /// It returns after the start code and the slots are written into the DMA buffer.
+    pub fn send(&mut self, start_code: u8, slots: &[u8]) -> Result<(), Error> {
+        if slots.len() > DMX_SLOTS {
+            return Err(Error::Other);
+        }
+
+        self.wait_tx_complete()?;
+        self.uart.set_baudrate(DMX_BREAK_BAUDRATE);
+        let rst = self
+            .tx
+            .write_all(&[0])
+            .and_then(|_| self.wait_tx_complete());
+        self.uart.set_baudrate(DMX_BAUDRATE);
+        rst?;
+
+        self.tx.write_all(&[start_code])?;
+        self.tx.write_all(slots)
+    }
+
+    fn wait_tx_complete(&mut self) -> Result<(), Error> {
+        self.tx.flush()?;
+        let mut t = Timeout::<OS>::micros(self.break_timeout.to_micros());
+        while !self.uart.is_tx_complete() {
+            if t.timeout() {
+                return Err(Error::Timeout);
+            }
+        }
+        Ok(())
+    }
+}
+
+// RX -------------------------------------------------------------------------
+
+/// The start code, slots and a possible break character
+const DMX_RX_BUF_SIZE: usize = DMX_SLOTS + 2;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct DmxFrameInfo {
+    pub start_code: u8,
+    /// The number of slots
+    pub len: usize,
+    /// No receive error occurred and the packet is not too long.
+    pub valid: bool,
+    /// It's increased by every packet received.
+    pub sequence: usize,
+}
+
+/// Shared between the receiver and its interrupt handler.
+struct DmxShared {
+    /// Written by the DMA, so they're only accessed by raw pointers.
+    bufs: [Vec<UnsafeCell<u8>>; 2],
+    /// It's odd while a new frame is being published.
+    sequence: AtomicUsize,
+    /// The length of the frame, including the start code, and the flags
+    frame: AtomicUsize,
+}
+
+const FRAME_BUF_1: usize = 1 << 16;
+const FRAME_VALID: usize = 1 << 17;
+const FRAME_LEN_MASK: usize = 0xFFFF;
+
+impl DmxShared {
+    fn new() -> Self {
+        Self {
+            bufs: [
+                (0..DMX_RX_BUF_SIZE).map(|_| UnsafeCell::new(0)).collect(),
+                (0..DMX_RX_BUF_SIZE).map(|_| UnsafeCell::new(0)).collect(),
+            ],
+            sequence: AtomicUsize::new(0),
+            frame: AtomicUsize::new(0),
+        }
+    }
+
+    #[inline]
+    fn buf_ptr(&self, buf_idx: usize) -> *mut u8 {
+        UnsafeCell::raw_get(self.bufs[buf_idx].as_ptr())
+    }
+
+    /// Start receiving into the buffer `buf_idx`.
+    fn start_dma(&self, ch: &mut impl DmaChannel, buf_idx: usize) {
+        ch.set_memory_address(self.buf_ptr(buf_idx) as usize, true);
+        ch.set_transfer_length(DMX_RX_BUF_SIZE);
+        ch.start();
+    }
+
+    fn publish(&self, buf_idx: usize, len: usize, valid: bool) {
+        let mut frame = len & FRAME_LEN_MASK;
+        if buf_idx == 1 {
+            frame |= FRAME_BUF_1;
+        }
+        if valid {
+            frame |= FRAME_VALID;
+        }
+        self.sequence.fetch_add(1, Ordering::AcqRel);
+        self.frame.store(frame, Ordering::Release);
+        self.sequence.fetch_add(1, Ordering::AcqRel);
+    }
+
+    /// Copy the latest frame into `slots`. Returns `None` if no frame is received yet.
+    fn read(&self, slots: &mut [u8]) -> Option<DmxFrameInfo> {
+        loop {
+            let seq = self.sequence.load(Ordering::Acquire);
+            if seq == 0 {
+                return None;
+            } else if seq % 2 == 1 {
+                continue;
+            }
+
+            let frame = self.frame.load(Ordering::Acquire);
+            let buf = self.buf_ptr(usize::from(frame & FRAME_BUF_1 != 0));
+            let len = frame & FRAME_LEN_MASK;
+            let start_code = unsafe { core::ptr::read_volatile(buf) };
+            let n = len.saturating_sub(1).min(slots.len());
+            for (i, s) in slots[..n].iter_mut().enumerate() {
+                *s = unsafe { core::ptr::read_volatile(buf.add(i + 1)) };
+            }
+
+            // The buffer is reused by the DMA after the next frame is published
+            if self.sequence.load(Ordering::Acquire) == seq {
+                return Some(DmxFrameInfo {
+                    start_code,
+                    len: len.saturating_sub(1),
+                    valid: frame & FRAME_VALID != 0 && len > 0,
+                    sequence: seq / 2,
+                });
+            }
+        }
+    }
+}
+
+// The buffers are written by the DMA and the interrupt handler, and read by the receiver.
+// The sequence number guards the accesses.
+unsafe impl Sync for DmxShared {}
+
+/// It holds the latest packet received. The DMA writes the next packet into another buffer.
+pub struct DmxRx {
+    shared: Arc<DmxShared>,
+}
+
+impl DmxRx {
+    /// The UART should be configured with [`Config::dmx512`].
+    pub fn new<U, CH>(mut uart: U, mut dma_ch: CH) -> (Self, DmxRxHandler<U, CH>)
+    where
+        U: UartPeriphWithDma,
+        CH: DmaChannel,
+    {
+        let shared = Arc::new(DmxShared::new());
+        dma_ch.stop();
+        dma_ch.set_peripheral_address::<u8>(uart.get_rx_data_reg_addr(), false, false, false);
+        shared.start_dma(&mut dma_ch, 0);
+        uart.enable_dma_rx(true);
+        uart.set_interrupt(Event::RxError, true);
+        (
+            Self {
+                shared: Arc::clone(&shared),
+            },
+            DmxRxHandler {
+                uart,
+                ch: dma_ch,
+                shared,
+                active: 0,
+                error: false,
+                started: false,
+            },
+        )
+    }
+
+    /// Copy the slots of the latest packet into `slots`.
+    /// Returns `None` if no packet is received yet.
+    /// Compare [`DmxFrameInfo::sequence`] to find out whether it's a new packet.
+    pub fn read_frame(&mut self, slots: &mut [u8]) -> Option<DmxFrameInfo> {
+        self.shared.read(slots)
+    }
+}
+
+pub struct DmxRxHandler<U, CH> {
+    uart: U,
+    ch: CH,
+    shared: Arc<DmxShared>,
+    /// The buffer that the DMA is writing
+    active: usize,
+    /// A receive error occurred in the current packet.
+    error: bool,
+    /// The first break is received, the data before it is dropped.
+    started: bool,
+}
+
+impl<U, CH> DmxRxHandler<U, CH>
+where
+    U: UartPeriph,
+    CH: DmaChannel,
+{
+    /// Call it in the UART interrupt callback.
+    pub fn handler(&mut self) {
+        if self.uart.check_and_clear_interrupt(Event::RxError) {
+            let err = self.uart.get_rx_error();
+            self.on_error(err);
+        }
+    }
+
+    fn on_error(&mut self, err: Option<Error>) {
+        match err {
+            Some(Error::FrameFormat) => self.on_break(),
+            Some(_) => {
+                self.error = true;
+                // The flags are cleared by the next DMA read of the data register,
+                // but there is no more read after the transfer is complete.
+                if !self.ch.in_progress() {
+                    self.uart.clear_err_flag();
+                }
+            }
+            None => (),
+        }
+    }
+
+    fn on_break(&mut self) {
+        // The transfer length is cleared when the channel is stopped
+        let mut len = DMX_RX_BUF_SIZE - self.ch.get_unprocessed_len();
+        self.ch.stop();
+        let buf = self.shared.buf_ptr(self.active);
+
+        // Get the character with the framing error
+        let data = match self.uart.read() {
+            Ok(data) => data as u8,
+            Err(_) if len > 0 => {
+                // It's already stored by the DMA
+                len -= 1;
+                unsafe { core::ptr::read_volatile(buf.add(len)) }
+            }
+            Err(_) => 0,
+        };
+
+        if self.started {
+            // A real framing error if the data is not 0
+            let valid = !self.error && data == 0 && len <= DMX_SLOTS + 1;
+            self.shared.publish(self.active, len, valid);
+            self.active ^= 1;
+        }
+        self.started = data == 0;
+        self.error = false;
+        self.shared.start_dma(&mut self.ch, self.active);
+    }
+}
+
+impl<U, CH> UartEventHandler for DmxRxHandler<U, CH>
+where
+    U: UartPeriph,
+    CH: DmaChannel,
+{
+    #[inline]
+    fn on_event(&mut self, status: &UartStatus) {
+        if status.rx_error.is_some() && self.uart.is_interrupt_enable(Event::RxError) {
+            self.on_error(status.rx_error);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::{dma::mock::MockDma, uart::mock::MockUart};
+
+    fn write_buf(shared: &DmxShared, buf_idx: usize, data: &[u8]) {
+        unsafe {
+            core::ptr::copy_nonoverlapping(data.as_ptr(), shared.buf_ptr(buf_idx), data.len())
+        };
+    }
+
+    #[test]
+    fn double_buffer() {
+        let shared = DmxShared::new();
+        let mut slots = [0u8; 4];
+        assert_eq!(shared.read(&mut slots), None);
+
+        write_buf(&shared, 0, &[0, 1, 2, 3]);
+        shared.publish(0, 4, true);
+        assert_eq!(
+            shared.read(&mut slots),
+            Some(DmxFrameInfo {
+                start_code: 0,
+                len: 3,
+                valid: true,
+                sequence: 1,
+            })
+        );
+        assert_eq!(slots, [1, 2, 3, 0]);
+
+        // Longer than the given buffer
+        write_buf(&shared, 1, &[0xCC, 6, 7, 8, 9, 10, 11]);
+        shared.publish(1, 7, false);
+        assert_eq!(
+            shared.read(&mut slots),
+            Some(DmxFrameInfo {
+                start_code: 0xCC,
+                len: 6,
+                valid: false,
+                sequence: 2,
+            })
+        );
+        assert_eq!(slots, [6, 7, 8, 9]);
+
+        // Empty packet
+        shared.publish(0, 0, true);
+        assert_eq!(
+            shared.read(&mut slots).map(|f| (f.len, f.valid)),
+            Some((0, false))
+        );
+    }
+
+    #[test]
+    fn receive() {
+        let uart = MockUart::default();
+        let dma = MockDma::default();
+        let (mut rx, mut handler) = DmxRx::new(uart.clone(), dma.clone());
+        let mut slots = [0u8; 4];
+
+        // The data before the first break are dropped.
+        // The break is already read by the DMA.
+        dma.receive(&[0xAA, 1, 2, 0], None);
+        uart.0.borrow_mut().rx_error = Some(Error::FrameFormat);
+        handler.handler();
+        assert_eq!(rx.read_frame(&mut slots), None);
+        assert!(dma.is_enabled());
+
+        dma.receive(&[0, 10, 20, 30, 0], None);
+        uart.0.borrow_mut().rx_error = Some(Error::FrameFormat);
+        handler.handler();
+        assert_eq!(
+            rx.read_frame(&mut slots),
+            Some(DmxFrameInfo {
+                start_code: 0,
+                len: 3,
+                valid: true,
+                sequence: 1,
+            })
+        );
+        assert_eq!(slots[..3], [10, 20, 30]);
+
+        // The break is still in the data register
+        dma.receive(&[0x17, 7], None);
+        uart.receive(0, Some(Error::FrameFormat));
+        handler.handler();
+        assert_eq!(
+            rx.read_frame(&mut slots),
+            Some(DmxFrameInfo {
+                start_code: 0x17,
+                len: 1,
+                valid: true,
+                sequence: 2,
+            })
+        );
+        assert_eq!(slots[0], 7);
+
+        // A noise error invalidates the packet
+        dma.receive(&[0, 1, 2], None);
+        uart.0.borrow_mut().rx_error = Some(Error::Noise);
+        handler.handler();
+        dma.receive(&[3, 0], None);
+        uart.0.borrow_mut().rx_error = Some(Error::FrameFormat);
+        handler.handler();
+        assert_eq!(
+            rx.read_frame(&mut slots).map(|f| (f.len, f.valid)),
+            Some((3, false))
+        );
+
+        // The flags are cleared after the transfer is complete
+        dma.receive(&[0; DMX_RX_BUF_SIZE + 1], None);
+        assert!(!dma.in_progress());
+        uart.0.borrow_mut().rx_error = Some(Error::Overrun);
+        handler.handler();
+        assert_eq!(uart.0.borrow().rx_error, None);
+    }
+}
@@ -0,0 +1,112 @@
+//! Single-wire half-duplex mode, TX and RX share the TX pin.
+//!
+//! Every transmitted byte is also received by the receiver, these echoed bytes
+//! are read and discarded while writing.
+
+use super::*;
+use crate::common::{embedded_io as e_io, os_trait::Timeout};
+
+pub struct UartHalfDuplex<U, OS> {
+    uart: U,
+    rx: UartPollRx<U, OS>,
+    timeout: MicrosDurationU32,
+    echo_timeout: MicrosDurationU32,
+    /// The error occurred after some bytes were written, it's returned in the next write.
+    err: Option<Error>,
+}
+
+impl<U: UartPeriph, OS: OsInterface> UartHalfDuplex<U, OS> {
+    /// `uart` is used to write, and `rx_uart` is used to read.
+    pub fn new(uart: [U; 2], baudrate: u32, timeout: MicrosDurationU32) -> Self {
+        let [uart, rx_uart] = uart;
+        Self {
+            uart,
+            rx: UartPollRx::new(rx_uart, baudrate, timeout),
+            timeout,
+            echo_timeout: calculate_timeout(baudrate, 4),
+            err: None,
+        }
+    }
+}
+
+/// All the data are sent in `write`, so there is nothing to wait for.
+impl<U: UartPeriph, OS: OsInterface> UartReconfigure for UartHalfDuplex<U, OS> {
+    #[inline]
+    fn suspend(&mut self, _timeout: MicrosDurationU32) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn resume(&mut self, baudrate: u32) {
+        self.echo_timeout = calculate_timeout(baudrate, 4);
+        self.err = None;
+        self.rx.resume(baudrate);
+    }
+}
+
+impl<U: UartPeriph, OS: OsInterface> e_io::ErrorType for UartHalfDuplex<U, OS> {
+    type Error = Error;
+}
+
+impl<U: UartPeriph, OS: OsInterface> e_io::Write for UartHalfDuplex<U, OS> {
+    /// Returns after all the written bytes are echoed.
+    /// Returns [`Error::Timeout`] if no echo is received.
+    ///
+    /// If an error occurs after some bytes are echoed, the number of them is returned,
+    /// and the error is returned from the next write.
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        if buf.is_empty() {
+            return Err(Error::Other);
+        }
+        if let Some(e) = self.err.take() {
+            return Err(e);
+        }
+
+        // Drop the data received before writing, otherwise it's counted as an echo
+        while let Ok(_) | Err(nb::Error::Other(_)) = self.uart.read() {}
+
+        let mut t = Timeout::<OS>::micros(self.timeout.to_micros());
+        let mut sent = 0;
+        let mut echoed = 0;
+        while echoed < buf.len() {
+            // One byte in the shift register and one in the data register,
+            // so the bytes are sent back to back without overrunning the receiver.
+            if sent < buf.len() && sent - echoed < 2 && self.uart.write(buf[sent] as u16).is_ok() {
+                sent += 1;
+            }
+
+            match self.uart.read() {
+                Ok(_) => {
+                    echoed += 1;
+                    t = Timeout::<OS>::micros(self.echo_timeout.to_micros());
+                }
+                Err(nb::Error::Other(e)) if echoed > 0 => {
+                    self.err = Some(e);
+                    return Ok(echoed);
+                }
+                Err(nb::Error::Other(e)) => return Err(e),
+                Err(nb::Error::WouldBlock) => {
+                    if t.timeout() {
+                        return if echoed > 0 {
+                            Ok(echoed)
+                        } else {
+                            Err(Error::Timeout)
+                        };
+                    }
+                }
+            }
+        }
+        Ok(echoed)
+    }
+
+    /// All the data are sent in `write`
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+}
+
+impl<U: UartPeriph, OS: OsInterface> e_io::Read for UartHalfDuplex<U, OS> {
+    #[inline]
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        e_io::Read::read(&mut self.rx, buf)
+    }
+}
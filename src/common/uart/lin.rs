@@ -0,0 +1,661 @@
+//! LIN (Local Interconnect Network) on top of the UART LIN mode.
+//!
+//! A frame is made of a header sent by the master and a response sent by the
+//! master or one of the slaves:
+//!
+//! ```text
+//! | break | sync 0x55 | protected ID | data 1..=8 | checksum |
+//! ```
+//!
+//! The TX and RX pins are connected to a LIN transceiver, so every byte on the
+//! bus is also received by the sender.
+
+use super::*;
+use crate::{common::os_trait::Timeout, l};
+use core::marker::PhantomData;
+
+pub const LIN_SYNC: u8 = 0x55;
+pub const LIN_MAX_DATA_LEN: usize = 8;
+
+pub trait LinPeriph: UartPeriph {
+    /// Enable or disable LIN mode. It should be called after the UART is configured.
+    fn set_lin_mode(&mut self, enable: bool, detection: LinBreakDetection);
+}
+
+/// Length of the break that the receiver detects
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum LinBreakDetection {
+    Bits10,
+    Bits11,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum LinChecksumModel {
+    /// LIN 1.x, only the data bytes are summed.
+    Classic,
+    /// LIN 2.x, the protected ID is summed too.
+    /// The diagnostic frames `0x3C` and `0x3D` always use the classic checksum.
+    Enhanced,
+}
+
+/// LIN error
+#[maybe_derive_format]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum LinError {
+    /// The parity bits of the protected ID are wrong.
+    Parity,
+    /// The checksum of the response is wrong.
+    Checksum,
+    /// The byte after the break is not `0x55`.
+    Sync,
+    /// The byte read back from the bus differs from the one sent.
+    Collision,
+    /// No response in time.
+    Timeout,
+    /// The data length is not from 1 to 8.
+    InvalidLength,
+    Uart(Error),
+}
+
+/// Add parity bits to a 6-bit frame ID.
+pub fn lin_pid(id: u8) -> u8 {
+    let id = id & 0x3F;
+    let bit = |n: u8| (id >> n) & 1;
+    let p0 = bit(0) ^ bit(1) ^ bit(2) ^ bit(4);
+    let p1 = !(bit(1) ^ bit(3) ^ bit(4) ^ bit(5)) & 1;
+    id | (p0 << 6) | (p1 << 7)
+}
+
+/// Check the parity bits and return the frame ID.
+pub fn lin_id(pid: u8) -> Result<u8, LinError> {
+    let id = pid & 0x3F;
+    if lin_pid(id) == pid {
+        Ok(id)
+    } else {
+        Err(LinError::Parity)
+    }
+}
+
+/// Inverted eight bit sum with carry.
+pub fn lin_checksum(model: LinChecksumModel, pid: u8, data: &[u8]) -> u8 {
+    let mut sum: u16 = match model {
+        LinChecksumModel::Enhanced if !matches!(pid & 0x3F, 0x3C | 0x3D) => pid as u16,
+        _ => 0,
+    };
+    for &d in data {
+        sum += d as u16;
+        if sum > 0xFF {
+            sum -= 0xFF;
+        }
+    }
+    !(sum as u8)
+}
+
+// Frame ----------------------------------------------------------------------
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum LinDirection {
+    /// The master sends the response.
+    MasterToSlave,
+    /// A slave sends the response.
+    SlaveToMaster,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct LinFrameInfo {
+    pub id: u8,
+    /// Length of the data, from 1 to 8
+    pub len: u8,
+    pub direction: LinDirection,
+}
+
+impl LinFrameInfo {
+    /// Returns [`LinError::InvalidLength`] if `len` is not from 1 to 8.
+    pub fn data_len(&self) -> Result<usize, LinError> {
+        check_len(self.len as usize)
+    }
+}
+
+fn check_len(len: usize) -> Result<usize, LinError> {
+    if (1..=LIN_MAX_DATA_LEN).contains(&len) {
+        Ok(len)
+    } else {
+        Err(LinError::InvalidLength)
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct LinFrame {
+    pub id: u8,
+    pub len: u8,
+    pub data: [u8; LIN_MAX_DATA_LEN],
+}
+
+impl LinFrame {
+    pub fn new(id: u8, data: &[u8]) -> Self {
+        l::assert!(data.len() <= LIN_MAX_DATA_LEN);
+        let mut d = [0; LIN_MAX_DATA_LEN];
+        d[..data.len()].copy_from_slice(data);
+        Self {
+            id,
+            len: data.len() as u8,
+            data: d,
+        }
+    }
+
+    #[inline]
+    pub fn data(&self) -> &[u8] {
+        &self.data[..self.len as usize]
+    }
+}
+
+// Schedule -------------------------------------------------------------------
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct LinScheduleEntry {
+    pub frame: LinFrameInfo,
+    /// The time from the start of this frame to the start of the next one
+    pub slot: MicrosDurationU32,
+}
+
+/// Iterate a schedule table of the master endlessly.
+pub struct LinSchedule<'a> {
+    table: &'a [LinScheduleEntry],
+    index: usize,
+}
+
+impl<'a> LinSchedule<'a> {
+    pub fn new(table: &'a [LinScheduleEntry]) -> Self {
+        Self { table, index: 0 }
+    }
+
+    /// Return `None` only if the table is empty.
+    pub fn next_entry(&mut self) -> Option<&'a LinScheduleEntry> {
+        let entry = self.table.get(self.index)?;
+        self.index = (self.index + 1) % self.table.len();
+        Some(entry)
+    }
+
+    /// Start from the first entry.
+    pub fn reset(&mut self) {
+        self.index = 0;
+    }
+
+    /// Switch to another schedule table.
+    pub fn set_table(&mut self, table: &'a [LinScheduleEntry]) {
+        self.table = table;
+        self.index = 0;
+    }
+}
+
+// Slave parser ---------------------------------------------------------------
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum LinSlaveEvent {
+    /// This slave should send the response of the frame now.
+    Respond(LinFrameInfo),
+    /// The response of a subscribed frame is received.
+    Received(LinFrame),
+    Error(LinError),
+}
+
+#[derive(Clone, Copy)]
+enum SlaveState {
+    /// Ignore everything until the next break.
+    Idle,
+    Sync,
+    Pid,
+    Data {
+        info: LinFrameInfo,
+        pid: u8,
+        n: u8,
+    },
+}
+
+/// Frame parser of a slave, it's fed with the break and the received bytes.
+pub struct LinSlaveParser<'a> {
+    frames: &'a [LinFrameInfo],
+    model: LinChecksumModel,
+    state: SlaveState,
+    data: [u8; LIN_MAX_DATA_LEN],
+}
+
+impl<'a> LinSlaveParser<'a> {
+    /// `frames`: the frames that this slave publishes or subscribes.
+    pub fn new(frames: &'a [LinFrameInfo], model: LinChecksumModel) -> Self {
+        Self {
+            frames,
+            model,
+            state: SlaveState::Idle,
+            data: [0; LIN_MAX_DATA_LEN],
+        }
+    }
+
+    /// A break is detected, a new frame is starting.
+    pub fn on_break(&mut self) {
+        self.state = SlaveState::Sync;
+    }
+
+    pub fn on_byte(&mut self, byte: u8) -> Option<LinSlaveEvent> {
+        match self.state {
+            SlaveState::Idle => None,
+            // The break itself is received as 0x00
+            SlaveState::Sync if byte == 0 => None,
+            SlaveState::Sync => {
+                if byte == LIN_SYNC {
+                    self.state = SlaveState::Pid;
+                    None
+                } else {
+                    self.state = SlaveState::Idle;
+                    Some(LinSlaveEvent::Error(LinError::Sync))
+                }
+            }
+            SlaveState::Pid => {
+                self.state = SlaveState::Idle;
+                let id = match lin_id(byte) {
+                    Ok(id) => id,
+                    Err(e) => return Some(LinSlaveEvent::Error(e)),
+                };
+                let info = *self.frames.iter().find(|f| f.id == id)?;
+                if let Err(e) = info.data_len() {
+                    return Some(LinSlaveEvent::Error(e));
+                }
+                match info.direction {
+                    LinDirection::SlaveToMaster => Some(LinSlaveEvent::Respond(info)),
+                    LinDirection::MasterToSlave => {
+                        self.state = SlaveState::Data {
+                            info,
+                            pid: byte,
+                            n: 0,
+                        };
+                        None
+                    }
+                }
+            }
+            SlaveState::Data { info, pid, n } => {
+                if n < info.len {
+                    self.data[n as usize] = byte;
+                    self.state = SlaveState::Data {
+                        info,
+                        pid,
+                        n: n + 1,
+                    };
+                    return None;
+                }
+
+                self.state = SlaveState::Idle;
+                let data = &self.data[..info.len as usize];
+                if lin_checksum(self.model, pid, data) == byte {
+                    Some(LinSlaveEvent::Received(LinFrame::new(info.id, data)))
+                } else {
+                    Some(LinSlaveEvent::Error(LinError::Checksum))
+                }
+            }
+        }
+    }
+}
+
+// Master ---------------------------------------------------------------------
+
+pub struct LinMaster<U, OS> {
+    uart: U,
+    model: LinChecksumModel,
+    byte_timeout: MicrosDurationU32,
+    _os: PhantomData<OS>,
+}
+
+impl<U: LinPeriph, OS: OsInterface> LinMaster<U, OS> {
+    pub fn new(uart: U, baudrate: u32, model: LinChecksumModel) -> Self {
+        Self {
+            uart,
+            model,
+            byte_timeout: calculate_timeout(baudrate, 4),
+            _os: PhantomData,
+        }
+    }
+
+    /// Send a frame with the response.
+    /// Returns [`LinError::InvalidLength`] if the length of `data` is not from 1 to 8.
+    pub fn write_frame(&mut self, id: u8, data: &[u8]) -> Result<(), LinError> {
+        check_len(data.len())?;
+        let pid = self.send_header(id)?;
+        for &b in data {
+            self.write_and_check(b)?;
+        }
+        self.write_and_check(lin_checksum(self.model, pid, data))
+    }
+
+    /// Send a header and read the response from a slave.
+    /// Returns [`LinError::InvalidLength`] if the length of `buf` is not from 1 to 8.
+    pub fn read_frame(&mut self, id: u8, buf: &mut [u8]) -> Result<(), LinError> {
+        check_len(buf.len())?;
+        let pid = self.send_header(id)?;
+        for b in buf.iter_mut() {
+            *b = self.read_byte()?;
+        }
+        if self.read_byte()? == lin_checksum(self.model, pid, buf) {
+            Ok(())
+        } else {
+            Err(LinError::Checksum)
+        }
+    }
+
+    /// Process the next frame of the schedule table and block until the slot ends.
+    ///
+    /// - `MasterToSlave`: `publish` fills the data, and then the frame is sent.
+    /// - `SlaveToMaster`: the received frame is returned.
+    ///
+    /// Return `None` if the table is empty.
+    pub fn run_slot(
+        &mut self,
+        schedule: &mut LinSchedule,
+        publish: impl FnOnce(u8, &mut [u8]),
+    ) -> Option<Result<LinFrame, LinError>> {
+        let entry = schedule.next_entry()?;
+        let mut t = Timeout::<OS>::micros(entry.slot.to_micros());
+
+        let info = entry.frame;
+        let rst = info.data_len().and_then(|len| {
+            let mut frame = LinFrame::new(info.id, &[0; LIN_MAX_DATA_LEN][..len]);
+            match info.direction {
+                LinDirection::MasterToSlave => {
+                    publish(info.id, &mut frame.data[..len]);
+                    self.write_frame(info.id, &frame.data[..len])
+                }
+                LinDirection::SlaveToMaster => self.read_frame(info.id, &mut frame.data[..len]),
+            }
+            .map(|_| frame)
+        });
+
+        while !t.timeout() {
+            OS::yield_task();
+        }
+        Some(rst)
+    }
+
+    pub fn release(self) -> U {
+        self.uart
+    }
+
+    fn send_header(&mut self, id: u8) -> Result<u8, LinError> {
+        // Drop the received data
+        while let Ok(_) | Err(nb::Error::Other(_)) = self.uart.read() {}
+
+        let pid = lin_pid(id);
+        self.uart.send_break();
+        self.write_byte(LIN_SYNC)?;
+        self.write_byte(pid)?;
+
+        // Read back the header, the break is received as 0x00 with a framing error.
+        let mut t = Timeout::<OS>::micros(self.byte_timeout.to_micros() * 2);
+        loop {
+            match self.uart.read() {
+                Ok(data) if data as u8 == LIN_SYNC => break,
+                Ok(_) | Err(nb::Error::Other(_)) => (),
+                Err(nb::Error::WouldBlock) => {
+                    if t.timeout() {
+                        return Err(LinError::Timeout);
+                    }
+                }
+            }
+        }
+        if self.read_byte()? == pid {
+            Ok(pid)
+        } else {
+            Err(LinError::Collision)
+        }
+    }
+
+    fn write_and_check(&mut self, byte: u8) -> Result<(), LinError> {
+        self.write_byte(byte)?;
+        if self.read_byte()? == byte {
+            Ok(())
+        } else {
+            Err(LinError::Collision)
+        }
+    }
+
+    fn write_byte(&mut self, byte: u8) -> Result<(), LinError> {
+        let mut t = Timeout::<OS>::micros(self.byte_timeout.to_micros());
+        loop {
+            match self.uart.write(byte as u16) {
+                Ok(()) => return Ok(()),
+                Err(nb::Error::Other(e)) => return Err(LinError::Uart(e)),
+                Err(nb::Error::WouldBlock) => {
+                    if t.timeout() {
+                        return Err(LinError::Timeout);
+                    }
+                }
+            }
+        }
+    }
+
+    fn read_byte(&mut self) -> Result<u8, LinError> {
+        let mut t = Timeout::<OS>::micros(self.byte_timeout.to_micros());
+        loop {
+            match self.uart.read() {
+                Ok(data) => return Ok(data as u8),
+                Err(nb::Error::Other(e)) => return Err(LinError::Uart(e)),
+                Err(nb::Error::WouldBlock) => {
+                    if t.timeout() {
+                        return Err(LinError::Timeout);
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl<U: LinPeriph, OS: OsInterface> UartReconfigure for LinMaster<U, OS> {
+    /// Every frame is complete before it returns.
+    #[inline]
+    fn suspend(&mut self, _timeout: MicrosDurationU32) -> Result<(), Error> {
+        Ok(())
+    }
+
+    #[inline]
+    fn resume(&mut self, baudrate: u32) {
+        self.byte_timeout = calculate_timeout(baudrate, 4);
+    }
+}
+
+// Slave ----------------------------------------------------------------------
+
+pub struct LinSlave<'a, U> {
+    uart: U,
+    parser: LinSlaveParser<'a>,
+    model: LinChecksumModel,
+}
+
+impl<'a, U: LinPeriph> LinSlave<'a, U> {
+    /// `frames`: the frames that this slave publishes or subscribes.
+    pub fn new(mut uart: U, frames: &'a [LinFrameInfo], model: LinChecksumModel) -> Self {
+        uart.check_and_clear_interrupt(Event::LinBreak);
+        Self {
+            uart,
+            parser: LinSlaveParser::new(frames, model),
+            model,
+        }
+    }
+
+    /// Enable the interrupts if `poll` is called in the UART interrupt callback.
+    pub fn listen(&mut self) {
+        self.uart.set_interrupt(Event::LinBreak, true);
+        self.uart.set_interrupt(Event::RxNotEmpty, true);
+    }
+
+    pub fn unlisten(&mut self) {
+        self.uart.set_interrupt(Event::LinBreak, false);
+        self.uart.set_interrupt(Event::RxNotEmpty, false);
+    }
+
+    /// Call it frequently or in the UART interrupt callback.
+    ///
+    /// `respond` fills the data of the frames that this slave publishes.
+    /// Return the received frame that this slave subscribes.
+    pub fn poll(
+        &mut self,
+        mut respond: impl FnMut(u8, &mut [u8]),
+    ) -> Option<Result<LinFrame, LinError>> {
+        if self.uart.check_and_clear_interrupt(Event::LinBreak) {
+            self.parser.on_break();
+        }
+
+        loop {
+            let byte = match self.uart.read() {
+                Ok(data) => data as u8,
+                // The break causes a framing error
+                Err(nb::Error::Other(Error::FrameFormat)) => continue,
+                Err(nb::Error::Other(e)) => return Some(Err(LinError::Uart(e))),
+                Err(nb::Error::WouldBlock) => return None,
+            };
+
+            match self.parser.on_byte(byte)? {
+                LinSlaveEvent::Respond(info) => {
+                    let pid = lin_pid(info.id);
+                    let mut data = [0; LIN_MAX_DATA_LEN];
+                    let data = &mut data[..info.len as usize];
+                    respond(info.id, data);
+                    for &b in data.iter() {
+                        self.write_byte(b);
+                    }
+                    self.write_byte(lin_checksum(self.model, pid, data));
+                }
+                LinSlaveEvent::Received(frame) => return Some(Ok(frame)),
+                LinSlaveEvent::Error(e) => return Some(Err(e)),
+            }
+        }
+    }
+
+    pub fn release(self) -> U {
+        self.uart
+    }
+
+    /// The echo is ignored by the parser until the next break.
+    fn write_byte(&mut self, byte: u8) {
+        while self.uart.write(byte as u16).is_err() {
+            // Discard the echo to avoid overrun
+            let _ = self.uart.read();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn protected_id() {
+        assert_eq!(lin_pid(0x00), 0x80);
+        assert_eq!(lin_pid(0x01), 0xC1);
+        assert_eq!(lin_pid(0x10), 0x50);
+        assert_eq!(lin_pid(0x22), 0xE2);
+        assert_eq!(lin_pid(0x3C), 0x3C);
+        assert_eq!(lin_pid(0x3D), 0x7D);
+
+        for id in 0..0x40 {
+            assert_eq!(lin_id(lin_pid(id)), Ok(id));
+        }
+        assert_eq!(lin_id(0x22), Err(LinError::Parity));
+    }
+
+    #[test]
+    fn checksum() {
+        let data = [0x4A, 0x55, 0x93, 0xE5];
+        assert_eq!(lin_checksum(LinChecksumModel::Classic, 0xE2, &data), 0xE6);
+        assert_eq!(lin_checksum(LinChecksumModel::Enhanced, 0xE2, &data), 0x04);
+        assert_eq!(lin_checksum(LinChecksumModel::Classic, 0, &[0xFF; 8]), 0);
+        // Diagnostic frames
+        assert_eq!(lin_checksum(LinChecksumModel::Enhanced, 0x3C, &data), 0xE6);
+        assert_eq!(lin_checksum(LinChecksumModel::Enhanced, 0x7D, &data), 0xE6);
+    }
+
+    const FRAMES: [LinFrameInfo; 2] = [
+        LinFrameInfo {
+            id: 0x22,
+            len: 4,
+            direction: LinDirection::MasterToSlave,
+        },
+        LinFrameInfo {
+            id: 0x10,
+            len: 2,
+            direction: LinDirection::SlaveToMaster,
+        },
+    ];
+
+    fn feed(p: &mut LinSlaveParser, bytes: &[u8]) -> Option<LinSlaveEvent> {
+        p.on_break();
+        let mut ret = None;
+        for &b in bytes {
+            if let Some(e) = p.on_byte(b) {
+                assert!(ret.is_none());
+                ret = Some(e);
+            }
+        }
+        ret
+    }
+
+    #[test]
+    fn slave_parser() {
+        let mut p = LinSlaveParser::new(&FRAMES, LinChecksumModel::Enhanced);
+
+        let ev = feed(&mut p, &[0x00, 0x55, 0xE2, 0x4A, 0x55, 0x93, 0xE5, 0x04]);
+        let frame = LinFrame::new(0x22, &[0x4A, 0x55, 0x93, 0xE5]);
+        assert_eq!(ev, Some(LinSlaveEvent::Received(frame)));
+
+        let ev = feed(&mut p, &[0x55, 0xE2, 0x4A, 0x55, 0x93, 0xE5, 0xE6]);
+        assert_eq!(ev, Some(LinSlaveEvent::Error(LinError::Checksum)));
+
+        let ev = feed(&mut p, &[0x00, 0x55, 0x50, 0x12, 0x34, 0x00]);
+        assert_eq!(ev, Some(LinSlaveEvent::Respond(FRAMES[1])));
+
+        // Unknown ID
+        assert_eq!(feed(&mut p, &[0x55, 0xC1, 0x12, 0x34]), None);
+        assert_eq!(
+            feed(&mut p, &[0x54, 0xE2]),
+            Some(LinSlaveEvent::Error(LinError::Sync))
+        );
+        assert_eq!(
+            feed(&mut p, &[0x55, 0x22]),
+            Some(LinSlaveEvent::Error(LinError::Parity))
+        );
+
+        // No break
+        assert_eq!(p.on_byte(0x55), None);
+        assert_eq!(p.on_byte(0xE2), None);
+
+        let frames = [LinFrameInfo {
+            id: 0x22,
+            len: 9,
+            direction: LinDirection::MasterToSlave,
+        }];
+        let mut p = LinSlaveParser::new(&frames, LinChecksumModel::Enhanced);
+        assert_eq!(
+            feed(&mut p, &[0x55, 0xE2]),
+            Some(LinSlaveEvent::Error(LinError::InvalidLength))
+        );
+    }
+
+    #[test]
+    fn schedule() {
+        let table = [
+            LinScheduleEntry {
+                frame: FRAMES[0],
+                slot: MicrosDurationU32::millis(10),
+            },
+            LinScheduleEntry {
+                frame: FRAMES[1],
+                slot: MicrosDurationU32::millis(20),
+            },
+        ];
+        let mut s = LinSchedule::new(&table);
+        assert_eq!(s.next_entry().unwrap().frame.id, 0x22);
+        assert_eq!(s.next_entry().unwrap().frame.id, 0x10);
+        assert_eq!(s.next_entry().unwrap().frame.id, 0x22);
+        s.reset();
+        assert_eq!(s.next_entry().unwrap().frame.id, 0x22);
+
+        s.set_table(&[]);
+        assert_eq!(s.next_entry(), None);
+    }
+}
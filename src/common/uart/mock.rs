@@ -0,0 +1,154 @@
+//! A UART for the host tests. The copies share the same registers, like the stolen peripherals.
+
+use super::*;
+use crate::Steal;
+use core::cell::RefCell;
+
+#[derive(Default)]
+pub(crate) struct MockUartState {
+    /// The data register, it's `Some` while RXNE is set.
+    pub rx: Option<u16>,
+    pub rx_error: Option<Error>,
+    pub idle: bool,
+    pub lin_break: bool,
+    pub enabled: Vec<Event>,
+    pub tx: Vec<u16>,
+    /// The number of reads of the data register
+    pub dr_reads: usize,
+}
+
+#[derive(Clone, Default)]
+pub(crate) struct MockUart(pub Arc<RefCell<MockUartState>>);
+
+impl MockUart {
+    /// A word is received, the error flags are set along with RXNE.
+    pub(crate) fn receive(&self, word: u16, err: Option<Error>) {
+        let mut s = self.0.borrow_mut();
+        s.rx = Some(word);
+        s.rx_error = err;
+    }
+
+    fn enabled(&self, event: Event) -> bool {
+        self.0.borrow().enabled.contains(&event)
+    }
+
+    /// Reading the data register clears RXNE, IDLE and the error flags.
+    fn read_dr(&self) -> Option<u16> {
+        let mut s = self.0.borrow_mut();
+        s.dr_reads += 1;
+        s.idle = false;
+        s.rx_error = None;
+        s.rx.take()
+    }
+}
+
+impl Steal for MockUart {
+    unsafe fn steal(&self) -> Self {
+        self.clone()
+    }
+}
+
+impl UartPeriph for MockUart {
+    fn set_baudrate(&mut self, _baudrate: u32) {}
+
+    fn write(&mut self, word: u16) -> nb::Result<(), Error> {
+        self.0.borrow_mut().tx.push(word);
+        Ok(())
+    }
+
+    fn is_tx_complete(&self) -> bool {
+        true
+    }
+
+    fn write_unchecked(&mut self, word: u16) {
+        self.0.borrow_mut().tx.push(word);
+    }
+
+    fn read(&mut self) -> nb::Result<u16, Error> {
+        if self.0.borrow().rx.is_some() {
+            return Ok(self.read_dr().unwrap());
+        }
+        match self.get_rx_error() {
+            Some(e) => {
+                self.clear_err_flag();
+                Err(nb::Error::Other(e))
+            }
+            None => Err(nb::Error::WouldBlock),
+        }
+    }
+
+    fn get_rx_error(&mut self) -> Option<Error> {
+        self.0.borrow().rx_error
+    }
+
+    fn get_status(&mut self) -> UartStatus {
+        let s = self.0.borrow();
+        UartStatus {
+            rx_not_empty: s.rx.is_some() && s.enabled.contains(&Event::RxNotEmpty),
+            tx_empty: s.enabled.contains(&Event::TxEmpty),
+            tx_complete: s.enabled.contains(&Event::TxComplete),
+            idle: s.idle && s.enabled.contains(&Event::Idle),
+            lin_break: s.lin_break && s.enabled.contains(&Event::LinBreak),
+            rx_error: s.rx_error,
+        }
+    }
+
+    fn disable_all_interrupt(&mut self) {
+        self.0.borrow_mut().enabled.clear();
+    }
+
+    fn set_interrupt(&mut self, event: Event, enable: bool) {
+        let mut s = self.0.borrow_mut();
+        s.enabled.retain(|e| *e != event);
+        if enable {
+            s.enabled.push(event);
+        }
+    }
+
+    fn is_interrupt_enable(&mut self, event: Event) -> bool {
+        self.enabled(event)
+    }
+
+    fn check_and_clear_interrupt(&mut self, event: Event) -> bool {
+        let enabled = self.enabled(event);
+        let s = self.0.borrow();
+        match event {
+            Event::Idle if s.idle && enabled => {
+                drop(s);
+                self.clear_err_flag();
+                true
+            }
+            Event::LinBreak if s.lin_break => {
+                drop(s);
+                self.0.borrow_mut().lin_break = false;
+                true
+            }
+            Event::RxNotEmpty => s.rx.is_some() && enabled,
+            Event::RxError => s.rx_error.is_some() && enabled,
+            Event::TxEmpty | Event::TxComplete => enabled,
+            _ => false,
+        }
+    }
+
+    fn clear_err_flag(&self) {
+        self.read_dr();
+    }
+
+    fn clear_tx_complete_flag(&mut self) {}
+
+    fn send_break(&mut self) {}
+}
+
+impl UartPeriphWithDma for MockUart {
+    fn get_tx_data_reg_addr(&self) -> usize {
+        0
+    }
+
+    fn get_rx_data_reg_addr(&self) -> usize {
+        0
+    }
+
+    fn enable_dma_tx(&mut self, _enable: bool) {}
+
+    fn enable_dma_rx(&mut self, _enable: bool) {}
+}
@@ -1,8 +1,32 @@
+mod auto_baud;
+mod dispatcher;
+mod dmx;
+mod half_duplex;
+mod lin;
+#[cfg(test)]
+pub(crate) mod mock;
+mod modbus;
+mod rs485;
+mod rx_error;
+mod smartcard;
+mod synchronous;
 mod uart_dma;
+mod uart_idle;
 mod uart_it;
 mod uart_poll;
 
+pub use auto_baud::*;
+pub use dispatcher::*;
+pub use dmx::*;
+pub use half_duplex::*;
+pub use lin::*;
+pub use modbus::*;
+pub use rs485::*;
+pub use rx_error::*;
+pub use smartcard::*;
+pub use synchronous::*;
 pub use uart_dma::*;
+pub use uart_idle::*;
 pub use uart_it::*;
 pub use uart_poll::*;
 
@@ -39,15 +63,96 @@ impl<U: UartPeriph> UartIdleInterrupt<U> {
     }
 }
 
+/// Mute mode control, it can be used on a multiprocessor bus.
+pub struct UartMuteControl<U: UartPeriphMute> {
+    uart: U,
+}
+
+impl<U: UartPeriphMute> UartMuteControl<U> {
+    pub fn new(uart: U) -> Self {
+        Self { uart }
+    }
+
+    /// The receiver ignores the data until the wakeup condition is detected.
+    #[inline]
+    pub fn enter_mute(&mut self) {
+        self.uart.enter_mute();
+    }
+
+    #[inline]
+    pub fn is_muted(&self) -> bool {
+        self.uart.is_muted()
+    }
+}
+
+/// Data word of UART, `u16` is used for 9-bit data.
+pub trait UartWord: Copy + Default + 'static {
+    fn from_u16(word: u16) -> Self;
+    fn into_u16(self) -> u16;
+}
+
+impl UartWord for u8 {
+    #[inline(always)]
+    fn from_u16(word: u16) -> Self {
+        word as u8
+    }
+
+    #[inline(always)]
+    fn into_u16(self) -> u16 {
+        self as u16
+    }
+}
+
+impl UartWord for u16 {
+    #[inline(always)]
+    fn from_u16(word: u16) -> Self {
+        word
+    }
+
+    #[inline(always)]
+    fn into_u16(self) -> u16 {
+        self
+    }
+}
+
+/// A transmitter or receiver that follows a configuration change of a running UART,
+/// see `UartControl::reconfigure`.
+pub trait UartReconfigure {
+    /// Called before the configuration is changed. A transmitter waits until its buffered
+    /// data are sent, and a receiver stops receiving.
+    fn suspend(&mut self, timeout: MicrosDurationU32) -> Result<(), Error>;
+    /// Called after the configuration is changed. The unread data are discarded,
+    /// and the timeouts derived from the baud rate are updated.
+    fn resume(&mut self, baudrate: u32);
+}
+
+/// The side that is not used
+impl UartReconfigure for () {
+    #[inline]
+    fn suspend(&mut self, _timeout: MicrosDurationU32) -> Result<(), Error> {
+        Ok(())
+    }
+
+    #[inline]
+    fn resume(&mut self, _baudrate: u32) {}
+}
+
 // Peripheral Trait -----------------------------------------------------------
 
 pub trait UartPeriph {
+    /// It should not be changed during communication.
+    fn set_baudrate(&mut self, baudrate: u32);
     fn write(&mut self, word: u16) -> nb::Result<(), Error>;
     /// Transfer is empty and completed
     fn is_tx_complete(&self) -> bool;
     fn write_unchecked(&mut self, word: u16);
 
     fn read(&mut self) -> nb::Result<u16, Error>;
+    /// Check the receive error flags without clearing them.
+    /// They are cleared by the following read of the data register.
+    fn get_rx_error(&mut self) -> Option<Error>;
+    /// Read the pending events at once, no flag is cleared.
+    fn get_status(&mut self) -> UartStatus;
 
     fn disable_all_interrupt(&mut self);
     fn set_interrupt(&mut self, event: Event, enable: bool);
@@ -57,6 +162,10 @@ pub trait UartPeriph {
     fn check_and_clear_interrupt(&mut self, event: Event) -> bool;
 
     fn clear_err_flag(&self);
+    /// Writing 0 to TC, the other flags are not affected.
+    fn clear_tx_complete_flag(&mut self);
+    /// Send a break character after the current character.
+    fn send_break(&mut self);
 }
 
 pub trait UartPeriphWithDma: UartPeriph {
@@ -66,6 +175,25 @@ pub trait UartPeriphWithDma: UartPeriph {
     fn enable_dma_rx(&mut self, enable: bool);
 }
 
+/// Mute mode of the receiver
+pub trait UartPeriphMute: UartPeriph {
+    /// `address` is the 4-bit address of this node, only used in [`WakeupMethod::AddressMark`].
+    fn set_wakeup(&mut self, method: WakeupMethod, address: u8);
+    fn enter_mute(&mut self);
+    fn is_muted(&self) -> bool;
+}
+
+/// The condition that wakes up the receiver from mute mode
+#[maybe_derive_format]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum WakeupMethod {
+    /// Woken up by an idle frame.
+    IdleLine,
+    /// Woken up by a word whose MSB is 1 and the lower 4 bits match the node address.
+    /// The MSB is the 9th bit with 9-bit words, or the 8th bit with 8-bit words.
+    AddressMark,
+}
+
 #[maybe_derive_format]
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum Event {
@@ -75,6 +203,12 @@ pub enum Event {
     RxNotEmpty,
     /// Idle line state detected
     Idle,
+    /// Transmission of the last frame is complete
+    TxComplete,
+    /// LIN break detected
+    LinBreak,
+    /// Parity error, or framing, noise and overrun errors when DMA reception is enabled
+    RxError,
 }
 
 /// UART error
@@ -93,6 +227,11 @@ pub enum Error {
     Noise,
     /// UART is busy and cannot accept new data.
     Busy,
+    /// A break character is received, it can be used as a frame delimiter.
+    Break,
+    /// No data is received, no data can be written,
+    /// or the transmission is not complete before the timeout.
+    Timeout,
     /// A different error occurred. The original error may contain more information.
     Other,
 }
@@ -105,6 +244,8 @@ impl Display for Error {
             Self::Parity => write!(f, "UART parity error"),
             Self::Noise => write!(f, "UART noise error"),
             Self::Busy => write!(f, "UART busy"),
+            Self::Break => write!(f, "UART break received"),
+            Self::Timeout => write!(f, "UART timeout"),
             Self::Other => write!(f, "UART other error"),
         }
     }
@@ -121,6 +262,8 @@ impl embedded_io::Error for Error {
             Self::Parity => e_io::ErrorKind::InvalidData,
             Self::Noise => e_io::ErrorKind::InvalidData,
             Self::Busy => e_io::ErrorKind::WriteZero,
+            Self::Break => e_io::ErrorKind::Other,
+            Self::Timeout => e_io::ErrorKind::TimedOut,
             Self::Other => e_io::ErrorKind::Other,
         }
     }
@@ -135,6 +278,8 @@ impl e_nb::serial::Error for Error {
             Self::Parity => e_nb::serial::ErrorKind::Parity,
             Self::Noise => e_nb::serial::ErrorKind::Noise,
             Self::Busy => e_nb::serial::ErrorKind::Other,
+            Self::Break => e_nb::serial::ErrorKind::Other,
+            Self::Timeout => e_nb::serial::ErrorKind::Other,
             Self::Other => e_nb::serial::ErrorKind::Other,
         }
     }
@@ -166,6 +311,16 @@ pub enum StopBits {
     STOP1P5,
 }
 
+/// IrDA SIR mode
+#[maybe_derive_format]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum IrdaMode {
+    /// The pulse width is 3/16 bit duration.
+    Normal,
+    /// The pulse width is 3 periods of the low-power frequency (about 1.8432 MHz).
+    LowPower,
+}
+
 pub struct Config {
     pub baudrate: u32,
     pub word_length: WordLength,
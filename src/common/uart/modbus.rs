@@ -0,0 +1,1411 @@
+//! Modbus RTU master and slave.
+//!
+//! The codec functions are pure. The master and slave work on any transmitter implementing
+//! [`Write`] and receiver implementing [`Read`] + [`ReadReady`], such as the DMA and interrupt types.
+//!
+//! A frame is delimited by the length derived from its function code. If the length is unknown,
+//! the frame ends at a silent interval of 1.5 characters. The master waits for the silent
+//! interval of 3.5 characters before every request.
+
+use super::*;
+use crate::common::{
+    embedded_io::{self as e_io, Read, ReadReady, Write},
+    os_trait::Timeout,
+};
+use core::marker::PhantomData;
+
+pub const MODBUS_MAX_FRAME: usize = 256;
+
+const READ_COILS: u8 = 0x01;
+const READ_DISCRETE_INPUTS: u8 = 0x02;
+const READ_HOLDING_REGISTERS: u8 = 0x03;
+const READ_INPUT_REGISTERS: u8 = 0x04;
+const WRITE_SINGLE_COIL: u8 = 0x05;
+const WRITE_SINGLE_REGISTER: u8 = 0x06;
+const WRITE_MULTIPLE_COILS: u8 = 0x0F;
+const WRITE_MULTIPLE_REGISTERS: u8 = 0x10;
+const EXCEPTION_FLAG: u8 = 0x80;
+
+const MAX_READ_BITS: u16 = 2000;
+const MAX_READ_REGISTERS: u16 = 125;
+const MAX_WRITE_BITS: u16 = 1968;
+const MAX_WRITE_REGISTERS: u16 = 123;
+
+/// 3.5 characters of 11 bits, it's fixed to 1750 us above 19200 baud.
+pub fn modbus_frame_gap(baudrate: u32) -> MicrosDurationU32 {
+    if baudrate > 19200 {
+        MicrosDurationU32::micros(1750)
+    } else {
+        MicrosDurationU32::micros((11 * 7 * 1_000_000_u32).div_ceil(2 * baudrate))
+    }
+}
+
+/// 1.5 characters of 11 bits, the longest silence within a frame.
+/// It's fixed to 750 us above 19200 baud.
+pub fn modbus_char_gap(baudrate: u32) -> MicrosDurationU32 {
+    if baudrate > 19200 {
+        MicrosDurationU32::micros(750)
+    } else {
+        MicrosDurationU32::micros((11 * 3 * 1_000_000_u32).div_ceil(2 * baudrate))
+    }
+}
+
+pub fn modbus_crc16(data: &[u8]) -> u16 {
+    let mut crc = 0xFFFF;
+    for &b in data {
+        crc ^= b as u16;
+        for _ in 0..8 {
+            if crc & 1 != 0 {
+                crc = (crc >> 1) ^ 0xA001;
+            } else {
+                crc >>= 1;
+            }
+        }
+    }
+    crc
+}
+
+// Types ----------------------------------------------------------------------
+
+#[maybe_derive_format]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ModbusException {
+    IllegalFunction,
+    IllegalDataAddress,
+    IllegalDataValue,
+    ServerDeviceFailure,
+    Acknowledge,
+    ServerDeviceBusy,
+    Other(u8),
+}
+
+impl ModbusException {
+    pub fn code(self) -> u8 {
+        match self {
+            Self::IllegalFunction => 0x01,
+            Self::IllegalDataAddress => 0x02,
+            Self::IllegalDataValue => 0x03,
+            Self::ServerDeviceFailure => 0x04,
+            Self::Acknowledge => 0x05,
+            Self::ServerDeviceBusy => 0x06,
+            Self::Other(code) => code,
+        }
+    }
+
+    pub fn from_code(code: u8) -> Self {
+        match code {
+            0x01 => Self::IllegalFunction,
+            0x02 => Self::IllegalDataAddress,
+            0x03 => Self::IllegalDataValue,
+            0x04 => Self::ServerDeviceFailure,
+            0x05 => Self::Acknowledge,
+            0x06 => Self::ServerDeviceBusy,
+            code => Self::Other(code),
+        }
+    }
+}
+
+#[maybe_derive_format]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ModbusError {
+    /// The CRC of the received frame is wrong.
+    Crc,
+    /// The received frame is malformed.
+    InvalidFrame,
+    /// The request is out of range, or it's not supported for broadcast.
+    InvalidArgument,
+    /// The slave returned an exception, or the slave received an unsupported request.
+    Exception(ModbusException),
+    /// The response doesn't match the request.
+    UnexpectedResponse,
+    Timeout,
+    Io(e_io::ErrorKind),
+}
+
+impl Display for ModbusError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Crc => write!(f, "Modbus CRC error"),
+            Self::InvalidFrame => write!(f, "Modbus invalid frame"),
+            Self::InvalidArgument => write!(f, "Modbus invalid argument"),
+            Self::Exception(e) => write!(f, "Modbus exception {}", e.code()),
+            Self::UnexpectedResponse => write!(f, "Modbus unexpected response"),
+            Self::Timeout => write!(f, "Modbus timeout"),
+            Self::Io(kind) => write!(f, "Modbus IO error {kind:?}"),
+        }
+    }
+}
+
+impl core::error::Error for ModbusError {}
+
+#[inline]
+fn io_err(e: impl e_io::Error) -> ModbusError {
+    ModbusError::Io(e.kind())
+}
+
+#[maybe_derive_format]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ModbusRequest<'a> {
+    ReadCoils {
+        address: u16,
+        quantity: u16,
+    },
+    ReadDiscreteInputs {
+        address: u16,
+        quantity: u16,
+    },
+    ReadHoldingRegisters {
+        address: u16,
+        quantity: u16,
+    },
+    ReadInputRegisters {
+        address: u16,
+        quantity: u16,
+    },
+    WriteSingleCoil {
+        address: u16,
+        value: bool,
+    },
+    WriteSingleRegister {
+        address: u16,
+        value: u16,
+    },
+    /// `values` are packed bits, LSB first.
+    WriteMultipleCoils {
+        address: u16,
+        quantity: u16,
+        values: &'a [u8],
+    },
+    /// `values` are big-endian registers.
+    WriteMultipleRegisters {
+        address: u16,
+        values: &'a [u8],
+    },
+}
+
+impl ModbusRequest<'_> {
+    pub fn function(&self) -> u8 {
+        match self {
+            Self::ReadCoils { .. } => READ_COILS,
+            Self::ReadDiscreteInputs { .. } => READ_DISCRETE_INPUTS,
+            Self::ReadHoldingRegisters { .. } => READ_HOLDING_REGISTERS,
+            Self::ReadInputRegisters { .. } => READ_INPUT_REGISTERS,
+            Self::WriteSingleCoil { .. } => WRITE_SINGLE_COIL,
+            Self::WriteSingleRegister { .. } => WRITE_SINGLE_REGISTER,
+            Self::WriteMultipleCoils { .. } => WRITE_MULTIPLE_COILS,
+            Self::WriteMultipleRegisters { .. } => WRITE_MULTIPLE_REGISTERS,
+        }
+    }
+}
+
+#[maybe_derive_format]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ModbusResponse<'a> {
+    /// Packed bits, LSB first
+    ReadCoils(&'a [u8]),
+    /// Packed bits, LSB first
+    ReadDiscreteInputs(&'a [u8]),
+    /// Big-endian registers
+    ReadHoldingRegisters(&'a [u8]),
+    /// Big-endian registers
+    ReadInputRegisters(&'a [u8]),
+    WriteSingleCoil {
+        address: u16,
+        value: bool,
+    },
+    WriteSingleRegister {
+        address: u16,
+        value: u16,
+    },
+    WriteMultipleCoils {
+        address: u16,
+        quantity: u16,
+    },
+    WriteMultipleRegisters {
+        address: u16,
+        quantity: u16,
+    },
+    Exception {
+        /// The function code of the request
+        function: u8,
+        exception: ModbusException,
+    },
+}
+
+impl ModbusResponse<'_> {
+    /// The function code of the request
+    pub fn function(&self) -> u8 {
+        match self {
+            Self::ReadCoils(_) => READ_COILS,
+            Self::ReadDiscreteInputs(_) => READ_DISCRETE_INPUTS,
+            Self::ReadHoldingRegisters(_) => READ_HOLDING_REGISTERS,
+            Self::ReadInputRegisters(_) => READ_INPUT_REGISTERS,
+            Self::WriteSingleCoil { .. } => WRITE_SINGLE_COIL,
+            Self::WriteSingleRegister { .. } => WRITE_SINGLE_REGISTER,
+            Self::WriteMultipleCoils { .. } => WRITE_MULTIPLE_COILS,
+            Self::WriteMultipleRegisters { .. } => WRITE_MULTIPLE_REGISTERS,
+            Self::Exception { function, .. } => *function,
+        }
+    }
+}
+
+// Codec ----------------------------------------------------------------------
+
+struct FrameWriter<'a> {
+    buf: &'a mut [u8],
+    len: usize,
+}
+
+impl<'a> FrameWriter<'a> {
+    fn new(buf: &'a mut [u8], slave: u8, function: u8) -> Result<Self, ModbusError> {
+        let mut w = Self { buf, len: 0 };
+        w.u8(slave)?;
+        w.u8(function)?;
+        Ok(w)
+    }
+
+    fn u8(&mut self, value: u8) -> Result<(), ModbusError> {
+        let b = self
+            .buf
+            .get_mut(self.len)
+            .ok_or(ModbusError::InvalidArgument)?;
+        *b = value;
+        self.len += 1;
+        Ok(())
+    }
+
+    fn u16(&mut self, value: u16) -> Result<(), ModbusError> {
+        self.bytes(&value.to_be_bytes())
+    }
+
+    fn bytes(&mut self, data: &[u8]) -> Result<(), ModbusError> {
+        let end = self.len + data.len();
+        self.buf
+            .get_mut(self.len..end)
+            .ok_or(ModbusError::InvalidArgument)?
+            .copy_from_slice(data);
+        self.len = end;
+        Ok(())
+    }
+
+    /// Append the CRC and return the length of the frame.
+    fn finish(mut self) -> Result<usize, ModbusError> {
+        let crc = modbus_crc16(&self.buf[..self.len]);
+        self.bytes(&crc.to_le_bytes())?;
+        Ok(self.len)
+    }
+}
+
+#[inline]
+fn be16(data: &[u8], i: usize) -> u16 {
+    u16::from_be_bytes([data[i], data[i + 1]])
+}
+
+/// Check the CRC, and return the slave address, the function code and the data.
+fn check_frame(frame: &[u8]) -> Result<(u8, u8, &[u8]), ModbusError> {
+    if frame.len() < 4 {
+        return Err(ModbusError::InvalidFrame);
+    }
+    let (content, crc) = frame.split_at(frame.len() - 2);
+    if modbus_crc16(content) != u16::from_le_bytes([crc[0], crc[1]]) {
+        return Err(ModbusError::Crc);
+    }
+    Ok((content[0], content[1], &content[2..]))
+}
+
+fn coil_value(value: u16) -> Result<bool, ModbusError> {
+    match value {
+        0xFF00 => Ok(true),
+        0x0000 => Ok(false),
+        _ => Err(ModbusError::Exception(ModbusException::IllegalDataValue)),
+    }
+}
+
+/// Returns the total length of a frame if it's known from the received bytes,
+/// otherwise the number of bytes needed to know it.
+pub fn modbus_expected_len(frame: &[u8], request: bool) -> Result<usize, ModbusError> {
+    if frame.len() < 2 {
+        return Ok(2);
+    }
+
+    let function = frame[1];
+    let len = if !request && function & EXCEPTION_FLAG != 0 {
+        5
+    } else {
+        match (function, request) {
+            (READ_COILS..=WRITE_SINGLE_REGISTER, true) => 8,
+            (WRITE_SINGLE_COIL | WRITE_SINGLE_REGISTER, false) => 8,
+            (WRITE_MULTIPLE_COILS | WRITE_MULTIPLE_REGISTERS, false) => 8,
+            (WRITE_MULTIPLE_COILS | WRITE_MULTIPLE_REGISTERS, true) => match frame.get(6) {
+                Some(&count) => 9 + count as usize,
+                None => 7,
+            },
+            (READ_COILS..=READ_INPUT_REGISTERS, false) => match frame.get(2) {
+                Some(&count) => 5 + count as usize,
+                None => 3,
+            },
+            _ => return Err(ModbusError::Exception(ModbusException::IllegalFunction)),
+        }
+    };
+
+    if len > MODBUS_MAX_FRAME {
+        return Err(ModbusError::InvalidFrame);
+    }
+    Ok(len)
+}
+
+/// Returns the length of the frame.
+pub fn modbus_encode_request(
+    slave: u8,
+    request: &ModbusRequest,
+    buf: &mut [u8],
+) -> Result<usize, ModbusError> {
+    let mut w = FrameWriter::new(buf, slave, request.function())?;
+    match *request {
+        ModbusRequest::ReadCoils { address, quantity }
+        | ModbusRequest::ReadDiscreteInputs { address, quantity } => {
+            if !(1..=MAX_READ_BITS).contains(&quantity) {
+                return Err(ModbusError::InvalidArgument);
+            }
+            w.u16(address)?;
+            w.u16(quantity)?;
+        }
+        ModbusRequest::ReadHoldingRegisters { address, quantity }
+        | ModbusRequest::ReadInputRegisters { address, quantity } => {
+            if !(1..=MAX_READ_REGISTERS).contains(&quantity) {
+                return Err(ModbusError::InvalidArgument);
+            }
+            w.u16(address)?;
+            w.u16(quantity)?;
+        }
+        ModbusRequest::WriteSingleCoil { address, value } => {
+            w.u16(address)?;
+            w.u16(if value { 0xFF00 } else { 0x0000 })?;
+        }
+        ModbusRequest::WriteSingleRegister { address, value } => {
+            w.u16(address)?;
+            w.u16(value)?;
+        }
+        ModbusRequest::WriteMultipleCoils {
+            address,
+            quantity,
+            values,
+        } => {
+            if !(1..=MAX_WRITE_BITS).contains(&quantity)
+                || values.len() != quantity.div_ceil(8) as usize
+            {
+                return Err(ModbusError::InvalidArgument);
+            }
+            w.u16(address)?;
+            w.u16(quantity)?;
+            w.u8(values.len() as u8)?;
+            w.bytes(values)?;
+        }
+        ModbusRequest::WriteMultipleRegisters { address, values } => {
+            let quantity = values.len() / 2;
+            if !(1..=MAX_WRITE_REGISTERS as usize).contains(&quantity) || values.len() % 2 != 0 {
+                return Err(ModbusError::InvalidArgument);
+            }
+            w.u16(address)?;
+            w.u16(quantity as u16)?;
+            w.u8(values.len() as u8)?;
+            w.bytes(values)?;
+        }
+    }
+    w.finish()
+}
+
+/// Returns the slave address and the request.
+/// An unsupported function is returned as [`ModbusException::IllegalFunction`].
+pub fn modbus_decode_request(frame: &[u8]) -> Result<(u8, ModbusRequest<'_>), ModbusError> {
+    let (slave, function, data) = check_frame(frame)?;
+    let fixed = |len: usize| {
+        if data.len() == len {
+            Ok(())
+        } else {
+            Err(ModbusError::InvalidFrame)
+        }
+    };
+
+    let request = match function {
+        READ_COILS..=WRITE_SINGLE_REGISTER => {
+            fixed(4)?;
+            let address = be16(data, 0);
+            let value = be16(data, 2);
+            match function {
+                READ_COILS => ModbusRequest::ReadCoils {
+                    address,
+                    quantity: value,
+                },
+                READ_DISCRETE_INPUTS => ModbusRequest::ReadDiscreteInputs {
+                    address,
+                    quantity: value,
+                },
+                READ_HOLDING_REGISTERS => ModbusRequest::ReadHoldingRegisters {
+                    address,
+                    quantity: value,
+                },
+                READ_INPUT_REGISTERS => ModbusRequest::ReadInputRegisters {
+                    address,
+                    quantity: value,
+                },
+                WRITE_SINGLE_COIL => ModbusRequest::WriteSingleCoil {
+                    address,
+                    value: coil_value(value)?,
+                },
+                _ => ModbusRequest::WriteSingleRegister { address, value },
+            }
+        }
+        WRITE_MULTIPLE_COILS | WRITE_MULTIPLE_REGISTERS => {
+            if data.len() < 5 || data.len() != 5 + data[4] as usize {
+                return Err(ModbusError::InvalidFrame);
+            }
+            let address = be16(data, 0);
+            let quantity = be16(data, 2);
+            let values = &data[5..];
+            if function == WRITE_MULTIPLE_COILS {
+                if !(1..=MAX_WRITE_BITS).contains(&quantity)
+                    || values.len() != quantity.div_ceil(8) as usize
+                {
+                    return Err(ModbusError::Exception(ModbusException::IllegalDataValue));
+                }
+                ModbusRequest::WriteMultipleCoils {
+                    address,
+                    quantity,
+                    values,
+                }
+            } else {
+                if !(1..=MAX_WRITE_REGISTERS).contains(&quantity)
+                    || values.len() != quantity as usize * 2
+                {
+                    return Err(ModbusError::Exception(ModbusException::IllegalDataValue));
+                }
+                ModbusRequest::WriteMultipleRegisters { address, values }
+            }
+        }
+        _ => return Err(ModbusError::Exception(ModbusException::IllegalFunction)),
+    };
+    Ok((slave, request))
+}
+
+/// Returns the length of the frame.
+pub fn modbus_encode_response(
+    slave: u8,
+    response: &ModbusResponse,
+    buf: &mut [u8],
+) -> Result<usize, ModbusError> {
+    let function = match response {
+        ModbusResponse::Exception { function, .. } => function | EXCEPTION_FLAG,
+        _ => response.function(),
+    };
+    let mut w = FrameWriter::new(buf, slave, function)?;
+    match *response {
+        ModbusResponse::ReadCoils(data)
+        | ModbusResponse::ReadDiscreteInputs(data)
+        | ModbusResponse::ReadHoldingRegisters(data)
+        | ModbusResponse::ReadInputRegisters(data) => {
+            w.u8(u8::try_from(data.len()).map_err(|_| ModbusError::InvalidArgument)?)?;
+            w.bytes(data)?;
+        }
+        ModbusResponse::WriteSingleCoil { address, value } => {
+            w.u16(address)?;
+            w.u16(if value { 0xFF00 } else { 0x0000 })?;
+        }
+        ModbusResponse::WriteSingleRegister { address, value } => {
+            w.u16(address)?;
+            w.u16(value)?;
+        }
+        ModbusResponse::WriteMultipleCoils { address, quantity }
+        | ModbusResponse::WriteMultipleRegisters { address, quantity } => {
+            w.u16(address)?;
+            w.u16(quantity)?;
+        }
+        ModbusResponse::Exception { exception, .. } => {
+            w.u8(exception.code())?;
+        }
+    }
+    w.finish()
+}
+
+/// Returns the slave address and the response.
+pub fn modbus_decode_response(frame: &[u8]) -> Result<(u8, ModbusResponse<'_>), ModbusError> {
+    let (slave, function, data) = check_frame(frame)?;
+    let fixed = |len: usize| {
+        if data.len() == len {
+            Ok(())
+        } else {
+            Err(ModbusError::InvalidFrame)
+        }
+    };
+
+    if function & EXCEPTION_FLAG != 0 {
+        fixed(1)?;
+        return Ok((
+            slave,
+            ModbusResponse::Exception {
+                function: function & !EXCEPTION_FLAG,
+                exception: ModbusException::from_code(data[0]),
+            },
+        ));
+    }
+
+    let response = match function {
+        READ_COILS..=READ_INPUT_REGISTERS => {
+            if data.is_empty() || data.len() != 1 + data[0] as usize {
+                return Err(ModbusError::InvalidFrame);
+            }
+            let values = &data[1..];
+            match function {
+                READ_COILS => ModbusResponse::ReadCoils(values),
+                READ_DISCRETE_INPUTS => ModbusResponse::ReadDiscreteInputs(values),
+                READ_HOLDING_REGISTERS => ModbusResponse::ReadHoldingRegisters(values),
+                _ => ModbusResponse::ReadInputRegisters(values),
+            }
+        }
+        WRITE_SINGLE_COIL
+        | WRITE_SINGLE_REGISTER
+        | WRITE_MULTIPLE_COILS
+        | WRITE_MULTIPLE_REGISTERS => {
+            fixed(4)?;
+            let address = be16(data, 0);
+            let value = be16(data, 2);
+            match function {
+                WRITE_SINGLE_COIL => ModbusResponse::WriteSingleCoil {
+                    address,
+                    value: coil_value(value).map_err(|_| ModbusError::InvalidFrame)?,
+                },
+                WRITE_SINGLE_REGISTER => ModbusResponse::WriteSingleRegister { address, value },
+                WRITE_MULTIPLE_COILS => ModbusResponse::WriteMultipleCoils {
+                    address,
+                    quantity: value,
+                },
+                _ => ModbusResponse::WriteMultipleRegisters {
+                    address,
+                    quantity: value,
+                },
+            }
+        }
+        _ => return Err(ModbusError::UnexpectedResponse),
+    };
+    Ok((slave, response))
+}
+
+// Slave processing -----------------------------------------------------------
+
+/// The data model of a slave.
+/// The default implementations return [`ModbusException::IllegalDataAddress`].
+pub trait ModbusSlaveHandler {
+    fn read_coil(&mut self, _address: u16) -> Result<bool, ModbusException> {
+        Err(ModbusException::IllegalDataAddress)
+    }
+
+    fn read_discrete_input(&mut self, _address: u16) -> Result<bool, ModbusException> {
+        Err(ModbusException::IllegalDataAddress)
+    }
+
+    fn read_holding_register(&mut self, _address: u16) -> Result<u16, ModbusException> {
+        Err(ModbusException::IllegalDataAddress)
+    }
+
+    fn read_input_register(&mut self, _address: u16) -> Result<u16, ModbusException> {
+        Err(ModbusException::IllegalDataAddress)
+    }
+
+    /// Multiple coils are written one by one, the previous ones are kept if one fails.
+    fn write_coil(&mut self, _address: u16, _value: bool) -> Result<(), ModbusException> {
+        Err(ModbusException::IllegalDataAddress)
+    }
+
+    /// Multiple registers are written one by one, the previous ones are kept if one fails.
+    fn write_register(&mut self, _address: u16, _value: u16) -> Result<(), ModbusException> {
+        Err(ModbusException::IllegalDataAddress)
+    }
+}
+
+fn check_range(address: u16, quantity: u16, max: u16) -> Result<(), ModbusException> {
+    if !(1..=max).contains(&quantity) {
+        Err(ModbusException::IllegalDataValue)
+    } else if address as u32 + quantity as u32 > 0x1_0000 {
+        Err(ModbusException::IllegalDataAddress)
+    } else {
+        Ok(())
+    }
+}
+
+fn execute_request(
+    slave: u8,
+    request: &ModbusRequest,
+    handler: &mut impl ModbusSlaveHandler,
+    buf: &mut [u8],
+) -> Result<Result<usize, ModbusException>, ModbusError> {
+    let mut w = FrameWriter::new(buf, slave, request.function())?;
+    match *request {
+        ModbusRequest::ReadCoils { address, quantity }
+        | ModbusRequest::ReadDiscreteInputs { address, quantity } => {
+            if let Err(e) = check_range(address, quantity, MAX_READ_BITS) {
+                return Ok(Err(e));
+            }
+            let coils = request.function() == READ_COILS;
+            w.u8(quantity.div_ceil(8) as u8)?;
+            for byte in 0..quantity.div_ceil(8) {
+                let mut bits = 0;
+                for bit in 0..8.min(quantity - byte * 8) {
+                    let addr = address + byte * 8 + bit;
+                    let value = if coils {
+                        handler.read_coil(addr)
+                    } else {
+                        handler.read_discrete_input(addr)
+                    };
+                    match value {
+                        Ok(true) => bits |= 1 << bit,
+                        Ok(false) => (),
+                        Err(e) => return Ok(Err(e)),
+                    }
+                }
+                w.u8(bits)?;
+            }
+        }
+        ModbusRequest::ReadHoldingRegisters { address, quantity }
+        | ModbusRequest::ReadInputRegisters { address, quantity } => {
+            if let Err(e) = check_range(address, quantity, MAX_READ_REGISTERS) {
+                return Ok(Err(e));
+            }
+            let holding = request.function() == READ_HOLDING_REGISTERS;
+            w.u8(quantity as u8 * 2)?;
+            for i in 0..quantity {
+                let value = if holding {
+                    handler.read_holding_register(address + i)
+                } else {
+                    handler.read_input_register(address + i)
+                };
+                match value {
+                    Ok(v) => w.u16(v)?,
+                    Err(e) => return Ok(Err(e)),
+                }
+            }
+        }
+        ModbusRequest::WriteSingleCoil { address, value } => {
+            if let Err(e) = handler.write_coil(address, value) {
+                return Ok(Err(e));
+            }
+            w.u16(address)?;
+            w.u16(if value { 0xFF00 } else { 0x0000 })?;
+        }
+        ModbusRequest::WriteSingleRegister { address, value } => {
+            if let Err(e) = handler.write_register(address, value) {
+                return Ok(Err(e));
+            }
+            w.u16(address)?;
+            w.u16(value)?;
+        }
+        ModbusRequest::WriteMultipleCoils {
+            address,
+            quantity,
+            values,
+        } => {
+            if let Err(e) = check_range(address, quantity, MAX_WRITE_BITS) {
+                return Ok(Err(e));
+            }
+            for i in 0..quantity {
+                let value = values[i as usize / 8] & (1 << (i % 8)) != 0;
+                if let Err(e) = handler.write_coil(address + i, value) {
+                    return Ok(Err(e));
+                }
+            }
+            w.u16(address)?;
+            w.u16(quantity)?;
+        }
+        ModbusRequest::WriteMultipleRegisters { address, values } => {
+            let quantity = (values.len() / 2) as u16;
+            if let Err(e) = check_range(address, quantity, MAX_WRITE_REGISTERS) {
+                return Ok(Err(e));
+            }
+            for i in 0..quantity {
+                if let Err(e) = handler.write_register(address + i, be16(values, i as usize * 2)) {
+                    return Ok(Err(e));
+                }
+            }
+            w.u16(address)?;
+            w.u16(quantity)?;
+        }
+    }
+    w.finish().map(Ok)
+}
+
+/// Process a request frame for the slave at `address`, and encode the response into `buf`.
+///
+/// Returns the length of the response, or 0 if no response should be sent,
+/// for a broadcast or a request to another slave.
+pub fn modbus_process_request(
+    address: u8,
+    frame: &[u8],
+    handler: &mut impl ModbusSlaveHandler,
+    buf: &mut [u8],
+) -> Result<usize, ModbusError> {
+    let (slave, function, _) = check_frame(frame)?;
+    let broadcast = slave == 0;
+    if slave != address && !broadcast {
+        return Ok(0);
+    }
+
+    let rst = match modbus_decode_request(frame) {
+        Ok((_, request)) => {
+            let is_read = request.function() <= READ_INPUT_REGISTERS;
+            if broadcast && is_read {
+                return Ok(0);
+            }
+            execute_request(slave, &request, handler, buf)?
+        }
+        Err(ModbusError::Exception(e)) => Err(e),
+        Err(e) => return Err(e),
+    };
+
+    match rst {
+        _ if broadcast => Ok(0),
+        Ok(len) => Ok(len),
+        Err(exception) => modbus_encode_response(
+            slave,
+            &ModbusResponse::Exception {
+                function,
+                exception,
+            },
+            buf,
+        ),
+    }
+}
+
+// Transport ------------------------------------------------------------------
+
+/// Read and drop the data until the line is silent for `gap`.
+fn wait_silence<OS: OsInterface>(
+    rx: &mut (impl Read + ReadReady),
+    gap: MicrosDurationU32,
+    timeout: MicrosDurationU32,
+) -> Result<(), ModbusError> {
+    let mut t = Timeout::<OS>::micros(gap.to_micros());
+    let mut total = Timeout::<OS>::micros(timeout.to_micros());
+    let mut tmp = [0u8; 16];
+    loop {
+        if rx.read_ready().map_err(io_err)? {
+            rx.read(&mut tmp).map_err(io_err)?;
+            t.restart();
+        } else if t.timeout() {
+            return Ok(());
+        }
+
+        if total.timeout() {
+            return Err(ModbusError::Timeout);
+        }
+    }
+}
+
+/// Returns the length of the frame.
+///
+/// `timeout`: the time to wait for the first byte.
+/// `gap`: the time to wait for the following bytes, see [`modbus_char_gap`].
+fn read_frame<OS: OsInterface>(
+    rx: &mut (impl Read + ReadReady),
+    buf: &mut [u8; MODBUS_MAX_FRAME],
+    request: bool,
+    timeout: MicrosDurationU32,
+    gap: MicrosDurationU32,
+) -> Result<usize, ModbusError> {
+    let mut t = Timeout::<OS>::micros(timeout.to_micros());
+    let mut n = 0;
+    // The frame ends at a silent interval if its length is unknown
+    let mut silence = false;
+    let mut overflow = false;
+    loop {
+        let need = if silence {
+            buf.len()
+        } else {
+            match modbus_expected_len(&buf[..n], request) {
+                Ok(need) if n >= need => return Ok(need),
+                Ok(need) => need,
+                Err(_) => {
+                    silence = true;
+                    buf.len()
+                }
+            }
+        };
+
+        if rx.read_ready().map_err(io_err)? {
+            if n < need {
+                n += rx.read(&mut buf[n..need]).map_err(io_err)?;
+            } else {
+                rx.read(&mut [0; 16]).map_err(io_err)?;
+                overflow = true;
+            }
+            t = Timeout::<OS>::micros(gap.to_micros());
+        } else if t.timeout() {
+            return match (n, silence, overflow) {
+                (0, _, _) => Err(ModbusError::Timeout),
+                (_, true, false) => Ok(n),
+                _ => Err(ModbusError::InvalidFrame),
+            };
+        }
+    }
+}
+
+// Master ---------------------------------------------------------------------
+
+pub struct ModbusMaster<TX, RX, OS> {
+    tx: TX,
+    rx: RX,
+    frame_gap: MicrosDurationU32,
+    char_gap: MicrosDurationU32,
+    frame_timeout: MicrosDurationU32,
+    response_timeout: MicrosDurationU32,
+    buf: [u8; MODBUS_MAX_FRAME],
+    _os: PhantomData<OS>,
+}
+
+impl<TX, RX, OS> ModbusMaster<TX, RX, OS>
+where
+    TX: Write,
+    RX: Read + ReadReady,
+    OS: OsInterface,
+{
+    /// `response_timeout`: the time to wait for the first byte of a response.
+    pub fn new(tx: TX, rx: RX, baudrate: u32, response_timeout: MicrosDurationU32) -> Self {
+        Self {
+            tx,
+            rx,
+            frame_gap: modbus_frame_gap(baudrate),
+            char_gap: modbus_char_gap(baudrate),
+            frame_timeout: calculate_timeout(baudrate, MODBUS_MAX_FRAME),
+            response_timeout,
+            buf: [0; MODBUS_MAX_FRAME],
+            _os: PhantomData,
+        }
+    }
+
+    /// Send a request and wait for the response. Returns `None` for a broadcast (`slave` is 0).
+    /// An exception response is returned as [`ModbusError::Exception`].
+    pub fn send(
+        &mut self,
+        slave: u8,
+        request: &ModbusRequest,
+    ) -> Result<Option<ModbusResponse<'_>>, ModbusError> {
+        let len = modbus_encode_request(slave, request, &mut self.buf)?;
+        wait_silence::<OS>(&mut self.rx, self.frame_gap, self.frame_timeout)?;
+        self.tx.write_all(&self.buf[..len]).map_err(io_err)?;
+        self.tx.flush().map_err(io_err)?;
+        if slave == 0 {
+            return Ok(None);
+        }
+
+        let len = read_frame::<OS>(
+            &mut self.rx,
+            &mut self.buf,
+            false,
+            self.response_timeout,
+            self.char_gap,
+        )?;
+        let (addr, response) = modbus_decode_response(&self.buf[..len])?;
+        if addr != slave || response.function() != request.function() {
+            return Err(ModbusError::UnexpectedResponse);
+        }
+        if let ModbusResponse::Exception { exception, .. } = response {
+            return Err(ModbusError::Exception(exception));
+        }
+        Ok(Some(response))
+    }
+
+    pub fn read_coils(
+        &mut self,
+        slave: u8,
+        address: u16,
+        values: &mut [bool],
+    ) -> Result<(), ModbusError> {
+        let quantity = u16::try_from(values.len()).map_err(|_| ModbusError::InvalidArgument)?;
+        match self.send(slave, &ModbusRequest::ReadCoils { address, quantity })? {
+            Some(ModbusResponse::ReadCoils(bits)) => unpack_bits(bits, values),
+            _ => Err(ModbusError::InvalidArgument),
+        }
+    }
+
+    pub fn read_discrete_inputs(
+        &mut self,
+        slave: u8,
+        address: u16,
+        values: &mut [bool],
+    ) -> Result<(), ModbusError> {
+        let quantity = u16::try_from(values.len()).map_err(|_| ModbusError::InvalidArgument)?;
+        match self.send(
+            slave,
+            &ModbusRequest::ReadDiscreteInputs { address, quantity },
+        )? {
+            Some(ModbusResponse::ReadDiscreteInputs(bits)) => unpack_bits(bits, values),
+            _ => Err(ModbusError::InvalidArgument),
+        }
+    }
+
+    pub fn read_holding_registers(
+        &mut self,
+        slave: u8,
+        address: u16,
+        values: &mut [u16],
+    ) -> Result<(), ModbusError> {
+        let quantity = u16::try_from(values.len()).map_err(|_| ModbusError::InvalidArgument)?;
+        match self.send(
+            slave,
+            &ModbusRequest::ReadHoldingRegisters { address, quantity },
+        )? {
+            Some(ModbusResponse::ReadHoldingRegisters(data)) => unpack_registers(data, values),
+            _ => Err(ModbusError::InvalidArgument),
+        }
+    }
+
+    pub fn read_input_registers(
+        &mut self,
+        slave: u8,
+        address: u16,
+        values: &mut [u16],
+    ) -> Result<(), ModbusError> {
+        let quantity = u16::try_from(values.len()).map_err(|_| ModbusError::InvalidArgument)?;
+        match self.send(
+            slave,
+            &ModbusRequest::ReadInputRegisters { address, quantity },
+        )? {
+            Some(ModbusResponse::ReadInputRegisters(data)) => unpack_registers(data, values),
+            _ => Err(ModbusError::InvalidArgument),
+        }
+    }
+
+    pub fn write_single_coil(
+        &mut self,
+        slave: u8,
+        address: u16,
+        value: bool,
+    ) -> Result<(), ModbusError> {
+        self.send(slave, &ModbusRequest::WriteSingleCoil { address, value })?;
+        Ok(())
+    }
+
+    pub fn write_single_register(
+        &mut self,
+        slave: u8,
+        address: u16,
+        value: u16,
+    ) -> Result<(), ModbusError> {
+        self.send(
+            slave,
+            &ModbusRequest::WriteSingleRegister { address, value },
+        )?;
+        Ok(())
+    }
+
+    pub fn write_multiple_coils(
+        &mut self,
+        slave: u8,
+        address: u16,
+        values: &[bool],
+    ) -> Result<(), ModbusError> {
+        let mut bits = [0u8; MAX_WRITE_BITS.div_ceil(8) as usize];
+        if values.len() > MAX_WRITE_BITS as usize {
+            return Err(ModbusError::InvalidArgument);
+        }
+        for (i, &v) in values.iter().enumerate() {
+            if v {
+                bits[i / 8] |= 1 << (i % 8);
+            }
+        }
+        let request = ModbusRequest::WriteMultipleCoils {
+            address,
+            quantity: values.len() as u16,
+            values: &bits[..values.len().div_ceil(8)],
+        };
+        self.send(slave, &request)?;
+        Ok(())
+    }
+
+    pub fn write_multiple_registers(
+        &mut self,
+        slave: u8,
+        address: u16,
+        values: &[u16],
+    ) -> Result<(), ModbusError> {
+        let mut data = [0u8; MAX_WRITE_REGISTERS as usize * 2];
+        if values.len() > MAX_WRITE_REGISTERS as usize {
+            return Err(ModbusError::InvalidArgument);
+        }
+        for (d, v) in data.chunks_exact_mut(2).zip(values) {
+            d.copy_from_slice(&v.to_be_bytes());
+        }
+        let request = ModbusRequest::WriteMultipleRegisters {
+            address,
+            values: &data[..values.len() * 2],
+        };
+        self.send(slave, &request)?;
+        Ok(())
+    }
+
+    pub fn release(self) -> (TX, RX) {
+        (self.tx, self.rx)
+    }
+}
+
+fn unpack_bits(bits: &[u8], values: &mut [bool]) -> Result<(), ModbusError> {
+    if bits.len() != values.len().div_ceil(8) {
+        return Err(ModbusError::UnexpectedResponse);
+    }
+    for (i, v) in values.iter_mut().enumerate() {
+        *v = bits[i / 8] & (1 << (i % 8)) != 0;
+    }
+    Ok(())
+}
+
+fn unpack_registers(data: &[u8], values: &mut [u16]) -> Result<(), ModbusError> {
+    if data.len() != values.len() * 2 {
+        return Err(ModbusError::UnexpectedResponse);
+    }
+    for (i, v) in values.iter_mut().enumerate() {
+        *v = be16(data, i * 2);
+    }
+    Ok(())
+}
+
+// Slave ----------------------------------------------------------------------
+
+pub struct ModbusSlave<TX, RX, OS> {
+    tx: TX,
+    rx: RX,
+    address: u8,
+    frame_gap: MicrosDurationU32,
+    char_gap: MicrosDurationU32,
+    frame_timeout: MicrosDurationU32,
+    rx_buf: [u8; MODBUS_MAX_FRAME],
+    tx_buf: [u8; MODBUS_MAX_FRAME],
+    _os: PhantomData<OS>,
+}
+
+impl<TX, RX, OS> ModbusSlave<TX, RX, OS>
+where
+    TX: Write,
+    RX: Read + ReadReady,
+    OS: OsInterface,
+{
+    pub fn new(tx: TX, rx: RX, address: u8, baudrate: u32) -> Self {
+        Self {
+            tx,
+            rx,
+            address,
+            frame_gap: modbus_frame_gap(baudrate),
+            char_gap: modbus_char_gap(baudrate),
+            frame_timeout: calculate_timeout(baudrate, MODBUS_MAX_FRAME),
+            rx_buf: [0; MODBUS_MAX_FRAME],
+            tx_buf: [0; MODBUS_MAX_FRAME],
+            _os: PhantomData,
+        }
+    }
+
+    /// Receive and process a request if there is incoming data.
+    /// Returns `true` if a frame is received.
+    pub fn poll(&mut self, handler: &mut impl ModbusSlaveHandler) -> Result<bool, ModbusError> {
+        if !self.rx.read_ready().map_err(io_err)? {
+            return Ok(false);
+        }
+
+        let rst = read_frame::<OS>(
+            &mut self.rx,
+            &mut self.rx_buf,
+            true,
+            self.char_gap,
+            self.char_gap,
+        )
+        .and_then(|n| {
+            modbus_process_request(self.address, &self.rx_buf[..n], handler, &mut self.tx_buf)
+        });
+
+        match rst {
+            Ok(0) => Ok(true),
+            Ok(len) => {
+                // The response is a new frame after the silent interval
+                wait_silence::<OS>(&mut self.rx, self.frame_gap, self.frame_timeout)?;
+                self.tx.write_all(&self.tx_buf[..len]).map_err(io_err)?;
+                self.tx.flush().map_err(io_err)?;
+                Ok(true)
+            }
+            Err(e) => {
+                // Resynchronize with the next frame
+                wait_silence::<OS>(&mut self.rx, self.frame_gap, self.frame_timeout)?;
+                Err(e)
+            }
+        }
+    }
+
+    pub fn release(self) -> (TX, RX) {
+        (self.tx, self.rx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn crc() {
+        assert_eq!(modbus_crc16(&[0x01, 0x03, 0x00, 0x00, 0x00, 0x0A]), 0xCDC5);
+        let mut buf = [0; MODBUS_MAX_FRAME];
+        let req = ModbusRequest::ReadHoldingRegisters {
+            address: 0,
+            quantity: 10,
+        };
+        let len = modbus_encode_request(1, &req, &mut buf).unwrap();
+        assert_eq!(buf[..len], [0x01, 0x03, 0x00, 0x00, 0x00, 0x0A, 0xC5, 0xCD]);
+        assert_eq!(modbus_decode_request(&buf[..len]), Ok((1, req)));
+
+        buf[3] = 1;
+        assert_eq!(modbus_decode_request(&buf[..len]), Err(ModbusError::Crc));
+    }
+
+    #[test]
+    fn request_codec() {
+        let mut buf = [0; MODBUS_MAX_FRAME];
+        let requests = [
+            ModbusRequest::ReadCoils {
+                address: 0x13,
+                quantity: 19,
+            },
+            ModbusRequest::WriteSingleCoil {
+                address: 0xAC,
+                value: true,
+            },
+            ModbusRequest::WriteSingleRegister {
+                address: 1,
+                value: 3,
+            },
+            ModbusRequest::WriteMultipleCoils {
+                address: 0x13,
+                quantity: 10,
+                values: &[0xCD, 0x01],
+            },
+            ModbusRequest::WriteMultipleRegisters {
+                address: 1,
+                values: &[0x00, 0x0A, 0x01, 0x02],
+            },
+        ];
+        for req in requests {
+            let len = modbus_encode_request(0x11, &req, &mut buf).unwrap();
+            assert_eq!(modbus_expected_len(&buf[..7], true), Ok(len));
+            assert_eq!(modbus_decode_request(&buf[..len]), Ok((0x11, req)));
+        }
+
+        // Invalid quantity
+        let req = ModbusRequest::ReadInputRegisters {
+            address: 0,
+            quantity: 126,
+        };
+        assert_eq!(
+            modbus_encode_request(1, &req, &mut buf),
+            Err(ModbusError::InvalidArgument)
+        );
+        for quantity in [0, MAX_WRITE_REGISTERS + 1] {
+            let mut frame = [0x11, WRITE_MULTIPLE_REGISTERS, 0, 1, 0, 0, 0, 0, 0];
+            frame[4..6].copy_from_slice(&quantity.to_be_bytes());
+            let crc = modbus_crc16(&frame[..7]);
+            frame[7..].copy_from_slice(&crc.to_le_bytes());
+            assert_eq!(
+                modbus_decode_request(&frame),
+                Err(ModbusError::Exception(ModbusException::IllegalDataValue))
+            );
+        }
+    }
+
+    #[test]
+    fn timing() {
+        assert_eq!(modbus_frame_gap(9600).to_micros(), 4011);
+        assert_eq!(modbus_char_gap(9600).to_micros(), 1719);
+        assert_eq!(modbus_frame_gap(115200).to_micros(), 1750);
+        assert_eq!(modbus_char_gap(115200).to_micros(), 750);
+    }
+
+    #[test]
+    fn response_codec() {
+        let mut buf = [0; MODBUS_MAX_FRAME];
+        let responses = [
+            ModbusResponse::ReadCoils(&[0xCD, 0x6B, 0x05]),
+            ModbusResponse::ReadInputRegisters(&[0x00, 0x0A]),
+            ModbusResponse::WriteSingleCoil {
+                address: 0xAC,
+                value: false,
+            },
+            ModbusResponse::WriteMultipleRegisters {
+                address: 1,
+                quantity: 2,
+            },
+            ModbusResponse::Exception {
+                function: READ_HOLDING_REGISTERS,
+                exception: ModbusException::IllegalDataAddress,
+            },
+        ];
+        for resp in responses {
+            let len = modbus_encode_response(0x11, &resp, &mut buf).unwrap();
+            assert_eq!(modbus_expected_len(&buf[..3], false), Ok(len));
+            assert_eq!(modbus_decode_response(&buf[..len]), Ok((0x11, resp)));
+        }
+
+        assert_eq!(modbus_expected_len(&[0x11], false), Ok(2));
+        assert_eq!(modbus_expected_len(&[0x11, 0x03], false), Ok(3));
+        assert_eq!(modbus_expected_len(&[0x11, 0x10, 0, 0, 0, 1], true), Ok(7));
+        assert_eq!(
+            modbus_expected_len(&[0x11, 0x2B], true),
+            Err(ModbusError::Exception(ModbusException::IllegalFunction))
+        );
+    }
+
+    #[derive(Default)]
+    struct Slave {
+        coils: [bool; 16],
+        registers: [u16; 4],
+    }
+
+    impl ModbusSlaveHandler for Slave {
+        fn read_coil(&mut self, address: u16) -> Result<bool, ModbusException> {
+            self.coils
+                .get(address as usize)
+                .copied()
+                .ok_or(ModbusException::IllegalDataAddress)
+        }
+
+        fn read_holding_register(&mut self, address: u16) -> Result<u16, ModbusException> {
+            self.registers
+                .get(address as usize)
+                .copied()
+                .ok_or(ModbusException::IllegalDataAddress)
+        }
+
+        fn write_coil(&mut self, address: u16, value: bool) -> Result<(), ModbusException> {
+            *self
+                .coils
+                .get_mut(address as usize)
+                .ok_or(ModbusException::IllegalDataAddress)? = value;
+            Ok(())
+        }
+
+        fn write_register(&mut self, address: u16, value: u16) -> Result<(), ModbusException> {
+            *self
+                .registers
+                .get_mut(address as usize)
+                .ok_or(ModbusException::IllegalDataAddress)? = value;
+            Ok(())
+        }
+    }
+
+    fn process<'a>(
+        slave: &mut Slave,
+        buf: &'a mut [u8; MODBUS_MAX_FRAME],
+        addr: u8,
+        req: &ModbusRequest,
+    ) -> Option<ModbusResponse<'a>> {
+        let mut frame = [0; MODBUS_MAX_FRAME];
+        let len = modbus_encode_request(addr, req, &mut frame).unwrap();
+        let len = modbus_process_request(5, &frame[..len], slave, buf).unwrap();
+        if len == 0 {
+            return None;
+        }
+        let (a, resp) = modbus_decode_response(&buf[..len]).unwrap();
+        assert_eq!(a, addr);
+        Some(resp)
+    }
+
+    #[test]
+    fn slave() {
+        let mut s = Slave::default();
+        let mut b = [0; MODBUS_MAX_FRAME];
+
+        let req = ModbusRequest::WriteMultipleRegisters {
+            address: 1,
+            values: &[0x12, 0x34, 0x56, 0x78],
+        };
+        assert_eq!(
+            process(&mut s, &mut b, 5, &req),
+            Some(ModbusResponse::WriteMultipleRegisters {
+                address: 1,
+                quantity: 2,
+            })
+        );
+        assert_eq!(s.registers, [0, 0x1234, 0x5678, 0]);
+
+        let req = ModbusRequest::ReadHoldingRegisters {
+            address: 1,
+            quantity: 2,
+        };
+        assert_eq!(
+            process(&mut s, &mut b, 5, &req),
+            Some(ModbusResponse::ReadHoldingRegisters(&[
+                0x12, 0x34, 0x56, 0x78
+            ]))
+        );
+
+        // Out of range
+        let req = ModbusRequest::ReadHoldingRegisters {
+            address: 3,
+            quantity: 2,
+        };
+        assert_eq!(
+            process(&mut s, &mut b, 5, &req),
+            Some(ModbusResponse::Exception {
+                function: READ_HOLDING_REGISTERS,
+                exception: ModbusException::IllegalDataAddress,
+            })
+        );
+
+        // Broadcast and another slave
+        let req = ModbusRequest::WriteMultipleCoils {
+            address: 2,
+            quantity: 10,
+            values: &[0xFF, 0x02],
+        };
+        assert_eq!(process(&mut s, &mut b, 0, &req), None);
+        assert_eq!(
+            process(
+                &mut s,
+                &mut b,
+                6,
+                &ModbusRequest::WriteSingleCoil {
+                    address: 0,
+                    value: true
+                }
+            ),
+            None
+        );
+        let req = ModbusRequest::ReadCoils {
+            address: 0,
+            quantity: 12,
+        };
+        assert_eq!(
+            process(&mut s, &mut b, 5, &req),
+            Some(ModbusResponse::ReadCoils(&[0xFC, 0x0B]))
+        );
+
+        // Unsupported
+        let req = ModbusRequest::ReadInputRegisters {
+            address: 0,
+            quantity: 1,
+        };
+        assert_eq!(
+            process(&mut s, &mut b, 5, &req),
+            Some(ModbusResponse::Exception {
+                function: READ_INPUT_REGISTERS,
+                exception: ModbusException::IllegalDataAddress,
+            })
+        );
+        let mut frame = [5, 0x2B, 0x0E, 0x01, 0, 0];
+        let crc = modbus_crc16(&frame[..4]).to_le_bytes();
+        frame[4..].copy_from_slice(&crc);
+        let mut buf = [0; MODBUS_MAX_FRAME];
+        let len = modbus_process_request(5, &frame, &mut s, &mut buf).unwrap();
+        assert_eq!(
+            modbus_decode_response(&buf[..len]),
+            Ok((
+                5,
+                ModbusResponse::Exception {
+                    function: 0x2B,
+                    exception: ModbusException::IllegalFunction,
+                }
+            ))
+        );
+    }
+}
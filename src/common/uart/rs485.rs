@@ -0,0 +1,313 @@
+//! RS-485 half-duplex transmitter.
+//!
+//! The driver-enable (DE) pin of the transceiver is asserted before the first
+//! byte is written, and deasserted when the transmission complete (TC) flag is set.
+//!
+//! - Poll mode: DE is deasserted in `flush`.
+//! - Interrupt and DMA mode: DE is deasserted in the UART interrupt.
+
+use super::*;
+use crate::common::{
+    critical_section::Mutex,
+    dma::{DmaChannel, DmaRingbufTxLoader},
+    embedded_hal::digital::OutputPin,
+    embedded_io::{ErrorType, Write, WriteReady},
+};
+use core::cell::RefCell;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    Poll,
+    Interrupt,
+    Dma,
+}
+
+struct DriverEnable<U, DE> {
+    uart: U,
+    de: DE,
+    mode: Mode,
+    asserted: bool,
+    writing: bool,
+}
+
+impl<U, DE> DriverEnable<U, DE>
+where
+    U: UartPeriph,
+    DE: OutputPin,
+{
+    fn new(uart: U, mut de: DE, mode: Mode) -> Arc<Mutex<RefCell<Self>>> {
+        de.set_low().ok();
+        Arc::new(Mutex::new(RefCell::new(Self {
+            uart,
+            de,
+            mode,
+            asserted: false,
+            writing: false,
+        })))
+    }
+
+    fn assert(&mut self) {
+        if !self.asserted {
+            self.asserted = true;
+            // DMA writes the data register without reading the status register first,
+            // so the old TC flag has to be cleared manually.
+            if self.mode == Mode::Dma {
+                self.uart.clear_tx_complete_flag();
+            }
+            self.de.set_high().ok();
+        }
+    }
+
+    fn deassert(&mut self) {
+        self.asserted = false;
+        self.uart.set_interrupt(Event::TxComplete, false);
+        self.de.set_low().ok();
+    }
+
+    /// `idle`: All data have been written to the data register.
+    fn update(&mut self, idle: bool) {
+        if !self.asserted || self.writing {
+            return;
+        }
+
+        if self.uart.check_and_clear_interrupt(Event::TxComplete) {
+            if idle {
+                self.deassert();
+            } else {
+                self.uart.set_interrupt(Event::TxComplete, false);
+            }
+        } else if idle && !self.uart.is_interrupt_enable(Event::TxComplete) {
+            self.uart.set_interrupt(Event::TxComplete, true);
+        }
+    }
+}
+
+// TX -------------------------------------------------------------------------
+
+pub struct Rs485Tx<W, U, DE> {
+    w: W,
+    de: Arc<Mutex<RefCell<DriverEnable<U, DE>>>>,
+}
+
+impl<U, DE, OS> Rs485Tx<UartPollTx<U, OS>, U, DE>
+where
+    U: UartPeriph,
+    DE: OutputPin,
+    OS: OsInterface,
+{
+    pub fn new_poll(w: UartPollTx<U, OS>, uart: U, de: DE) -> Self {
+        Self {
+            w,
+            de: DriverEnable::new(uart, de, Mode::Poll),
+        }
+    }
+}
+
+impl<U, DE, OS> Rs485Tx<UartInterruptTx<U, OS>, U, DE>
+where
+    U: UartPeriph,
+    DE: OutputPin,
+    OS: OsInterface,
+{
+    pub fn new_interrupt(
+        w: UartInterruptTx<U, OS>,
+        handler: UartInterruptTxHandler<U, OS>,
+        uart: U,
+        de: DE,
+    ) -> (Self, Rs485TxHandler<UartInterruptTxHandler<U, OS>, U, DE>) {
+        let de = DriverEnable::new(uart, de, Mode::Interrupt);
+        (
+            Self {
+                w,
+                de: Arc::clone(&de),
+            },
+            Rs485TxHandler { handler, de },
+        )
+    }
+}
+
+impl<U, DE, CH, OS> Rs485Tx<UartDmaBufTx<U, CH, OS>, U, DE>
+where
+    U: UartPeriph,
+    DE: OutputPin,
+    CH: DmaChannel,
+    OS: OsInterface,
+{
+    pub fn new_dma(
+        w: UartDmaBufTx<U, CH, OS>,
+        loader: DmaRingbufTxLoader<u8, CH, OS>,
+        uart: U,
+        de: DE,
+    ) -> (Self, Rs485TxHandler<DmaRingbufTxLoader<u8, CH, OS>, U, DE>) {
+        let de = DriverEnable::new(uart, de, Mode::Dma);
+        (
+            Self {
+                w,
+                de: Arc::clone(&de),
+            },
+            Rs485TxHandler {
+                handler: loader,
+                de,
+            },
+        )
+    }
+}
+
+impl<W, U, DE> Rs485Tx<W, U, DE>
+where
+    U: UartPeriph,
+    DE: OutputPin,
+{
+    /// Whether the driver is enabled
+    pub fn is_driver_enabled(&self) -> bool {
+        critical_section::with(|cs| self.de.borrow_ref(cs).asserted)
+    }
+}
+
+/// The driver is released by the handler after the transmission is complete.
+impl<W, U, DE> UartReconfigure for Rs485Tx<W, U, DE>
+where
+    W: UartReconfigure,
+{
+    #[inline]
+    fn suspend(&mut self, timeout: MicrosDurationU32) -> Result<(), Error> {
+        self.w.suspend(timeout)
+    }
+
+    #[inline]
+    fn resume(&mut self, baudrate: u32) {
+        self.w.resume(baudrate);
+    }
+}
+
+impl<W, U, DE> ErrorType for Rs485Tx<W, U, DE>
+where
+    W: ErrorType,
+{
+    type Error = W::Error;
+}
+
+impl<W, U, DE> Write for Rs485Tx<W, U, DE>
+where
+    W: Write,
+    U: UartPeriph,
+    DE: OutputPin,
+{
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        if buf.is_empty() {
+            return self.w.write(buf);
+        }
+
+        critical_section::with(|cs| {
+            let mut de = self.de.borrow_ref_mut(cs);
+            de.assert();
+            de.writing = true;
+        });
+
+        let rst = self.w.write(buf);
+
+        critical_section::with(|cs| {
+            let mut de = self.de.borrow_ref_mut(cs);
+            de.writing = false;
+            // Make sure the interrupt handler will check the TC flag
+            if de.mode != Mode::Poll {
+                de.uart.set_interrupt(Event::TxComplete, true);
+            }
+        });
+        rst
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        self.w.flush()?;
+        critical_section::with(|cs| {
+            let mut de = self.de.borrow_ref_mut(cs);
+            if de.asserted && de.uart.is_tx_complete() {
+                de.deassert();
+            }
+        });
+        Ok(())
+    }
+}
+
+impl<W, U, DE> WriteReady for Rs485Tx<W, U, DE>
+where
+    W: WriteReady,
+    U: UartPeriph,
+    DE: OutputPin,
+{
+    fn write_ready(&mut self) -> Result<bool, Self::Error> {
+        self.w.write_ready()
+    }
+}
+
+// TX interrupt -----------------
+
+/// Wraps the interrupt handler of the transmitter.
+pub struct Rs485TxHandler<H, U, DE> {
+    handler: H,
+    de: Arc<Mutex<RefCell<DriverEnable<U, DE>>>>,
+}
+
+impl<U, DE, OS> Rs485TxHandler<UartInterruptTxHandler<U, OS>, U, DE>
+where
+    U: UartPeriph,
+    DE: OutputPin,
+    OS: OsInterface,
+{
+    /// Call it in the UART interrupt callback.
+    pub fn handler(&mut self) {
+        self.handler.handler();
+        let idle = self.handler.is_empty();
+        critical_section::with(|cs| self.de.borrow_ref_mut(cs).update(idle));
+    }
+}
+
+impl<U, DE, OS> UartEventHandler for Rs485TxHandler<UartInterruptTxHandler<U, OS>, U, DE>
+where
+    U: UartPeriph,
+    DE: OutputPin,
+    OS: OsInterface,
+{
+    #[inline]
+    fn on_event(&mut self, status: &UartStatus) {
+        if status.tx_empty || status.tx_complete {
+            self.handler();
+        }
+    }
+}
+
+impl<U, DE, CH, OS> Rs485TxHandler<DmaRingbufTxLoader<u8, CH, OS>, U, DE>
+where
+    U: UartPeriph,
+    DE: OutputPin,
+    CH: DmaChannel,
+    OS: OsInterface,
+{
+    /// Call it in the DMA channel interrupt callback.
+    pub fn interrupt_reload(&mut self) {
+        self.handler.interrupt_reload();
+        self.interrupt_tx_complete();
+    }
+
+    /// Call it in the UART interrupt callback.
+    pub fn interrupt_tx_complete(&mut self) {
+        let idle = !self.handler.in_progress();
+        critical_section::with(|cs| self.de.borrow_ref_mut(cs).update(idle));
+    }
+}
+
+/// The DMA channel interrupt is not dispatched, call [`Rs485TxHandler::interrupt_reload`] for it.
+impl<U, DE, CH, OS> UartEventHandler for Rs485TxHandler<DmaRingbufTxLoader<u8, CH, OS>, U, DE>
+where
+    U: UartPeriph,
+    DE: OutputPin,
+    CH: DmaChannel,
+    OS: OsInterface,
+{
+    #[inline]
+    fn on_event(&mut self, status: &UartStatus) {
+        if status.tx_complete {
+            self.interrupt_tx_complete();
+        }
+    }
+}
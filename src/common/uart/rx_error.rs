@@ -0,0 +1,213 @@
+//! Receive errors captured by the interrupt handlers.
+//!
+//! The errors are counted, and the first pending error is returned from the next read.
+
+use super::*;
+use crate::common::atomic_cell::{AtomicCell, AtomicCellMember, Ordering};
+use core::sync::atomic::{AtomicU32, AtomicUsize};
+
+/// Counters of the receive errors
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct UartErrorStats {
+    pub overrun: u32,
+    pub frame_format: u32,
+    pub parity: u32,
+    pub noise: u32,
+}
+
+impl UartErrorStats {
+    pub fn total(&self) -> u32 {
+        self.overrun
+            .saturating_add(self.frame_format)
+            .saturating_add(self.parity)
+            .saturating_add(self.noise)
+    }
+}
+
+/// Shared between the receiver and its interrupt handler.
+pub(crate) struct RxErrorState {
+    overrun: AtomicU32,
+    frame_format: AtomicU32,
+    parity: AtomicU32,
+    noise: AtomicU32,
+    pending: AtomicCell<Option<Error>>,
+    /// The number of words received before the pending error
+    position: AtomicUsize,
+}
+
+impl RxErrorState {
+    pub fn new() -> Self {
+        Self {
+            overrun: AtomicU32::new(0),
+            frame_format: AtomicU32::new(0),
+            parity: AtomicU32::new(0),
+            noise: AtomicU32::new(0),
+            pending: AtomicCell::new(None),
+            position: AtomicUsize::new(0),
+        }
+    }
+
+    /// Called in the interrupt handler, [`Error::Break`] is not counted.
+    /// `position` is the number of words received before the error, it can wrap around.
+    pub fn record(&self, err: Error, position: usize) {
+        let counter = match err {
+            Error::Overrun => Some(&self.overrun),
+            Error::FrameFormat => Some(&self.frame_format),
+            Error::Parity => Some(&self.parity),
+            Error::Noise => Some(&self.noise),
+            _ => None,
+        };
+        if let Some(counter) = counter {
+            counter.fetch_add(1, Ordering::Relaxed);
+        }
+
+        // Keep the first error until it's read
+        if self.pending.load(Ordering::Acquire).is_none() {
+            self.position.store(position, Ordering::Relaxed);
+            self.pending.store(Some(err), Ordering::Release);
+        }
+    }
+
+    /// Called by the receiver, `read_count` is the number of words that have been read.
+    ///
+    /// Returns the number of words that can be read before the pending error,
+    /// or takes the pending error if it should be returned now.
+    pub fn check(&self, read_count: usize, mark_position: bool) -> Result<usize, Error> {
+        let Some(err) = self.pending.load(Ordering::Acquire) else {
+            return Ok(usize::MAX);
+        };
+
+        let n = self
+            .position
+            .load(Ordering::Relaxed)
+            .wrapping_sub(read_count);
+        if mark_position && n != 0 && n <= isize::MAX as usize {
+            return Ok(n);
+        }
+
+        self.pending.store(None, Ordering::Release);
+        Err(err)
+    }
+
+    /// Drop the pending error.
+    pub fn clear(&self) {
+        self.pending.store(None, Ordering::Release);
+    }
+
+    pub fn stats(&self) -> UartErrorStats {
+        UartErrorStats {
+            overrun: self.overrun.load(Ordering::Relaxed),
+            frame_format: self.frame_format.load(Ordering::Relaxed),
+            parity: self.parity.load(Ordering::Relaxed),
+            noise: self.noise.load(Ordering::Relaxed),
+        }
+    }
+
+    pub fn reset_stats(&self) {
+        self.overrun.store(0, Ordering::Relaxed);
+        self.frame_format.store(0, Ordering::Relaxed);
+        self.parity.store(0, Ordering::Relaxed);
+        self.noise.store(0, Ordering::Relaxed);
+    }
+}
+
+/// A break is received as `0` with a framing error.
+#[inline]
+pub(crate) fn is_break(err: Option<Error>, data: u16) -> bool {
+    err == Some(Error::FrameFormat) && data == 0
+}
+
+impl AtomicCellMember for Option<Error> {
+    #[inline]
+    fn to_num(self) -> usize {
+        match self {
+            None => 0,
+            Some(Error::Overrun) => 1,
+            Some(Error::FrameFormat) => 2,
+            Some(Error::Parity) => 3,
+            Some(Error::Noise) => 4,
+            Some(Error::Busy) => 5,
+            Some(Error::Break) => 6,
+            Some(Error::Timeout) => 7,
+            Some(Error::Other) => 8,
+        }
+    }
+
+    #[inline]
+    unsafe fn from_num(val: usize) -> Self {
+        match val {
+            0 => None,
+            1 => Some(Error::Overrun),
+            2 => Some(Error::FrameFormat),
+            3 => Some(Error::Parity),
+            4 => Some(Error::Noise),
+            5 => Some(Error::Busy),
+            6 => Some(Error::Break),
+            7 => Some(Error::Timeout),
+            _ => Some(Error::Other),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn error_code() {
+        for err in [
+            None,
+            Some(Error::Overrun),
+            Some(Error::FrameFormat),
+            Some(Error::Parity),
+            Some(Error::Noise),
+            Some(Error::Busy),
+            Some(Error::Break),
+            Some(Error::Timeout),
+            Some(Error::Other),
+        ] {
+            assert_eq!(err, unsafe { Option::<Error>::from_num(err.to_num()) });
+        }
+    }
+
+    #[test]
+    fn pending_error() {
+        let s = RxErrorState::new();
+        assert_eq!(s.check(0, true), Ok(usize::MAX));
+
+        // Without marking, the error is returned at once
+        s.record(Error::Noise, 5);
+        s.record(Error::Parity, 6);
+        assert_eq!(s.check(2, false), Err(Error::Noise));
+        assert_eq!(s.check(2, false), Ok(usize::MAX));
+
+        // The words before the error are read first
+        s.record(Error::Overrun, 10);
+        assert_eq!(s.check(7, true), Ok(3));
+        assert_eq!(s.check(10, true), Err(Error::Overrun));
+        assert_eq!(s.check(10, true), Ok(usize::MAX));
+
+        // Wrapping position
+        s.record(Error::FrameFormat, 2);
+        assert_eq!(s.check(usize::MAX - 1, true), Ok(4));
+        assert_eq!(s.check(2, true), Err(Error::FrameFormat));
+
+        // Break is not counted
+        s.record(Error::Break, 3);
+        assert_eq!(s.check(3, true), Err(Error::Break));
+
+        let stats = s.stats();
+        assert_eq!(
+            stats,
+            UartErrorStats {
+                overrun: 1,
+                frame_format: 1,
+                parity: 1,
+                noise: 1,
+            }
+        );
+        assert_eq!(stats.total(), 4);
+        s.reset_stats();
+        assert_eq!(s.stats(), UartErrorStats::default());
+    }
+}
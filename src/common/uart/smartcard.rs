@@ -0,0 +1,616 @@
+//! ISO 7816-3 smartcard with the T=0 protocol.
+//!
+//! The card I/O is connected to the TX pin (open-drain), and the card clock is
+//! provided by the CK pin. The reset pin of the card is a GPIO controlled by the user.
+//! Only the direct convention is supported.
+
+use super::*;
+use crate::common::os_trait::Timeout;
+use core::marker::PhantomData;
+
+pub const ATR_MAX_LEN: usize = 33;
+const ATR_MAX_HISTORICAL_LEN: usize = 15;
+/// Default work waiting time integer
+const DEFAULT_WI: u8 = 10;
+
+/// Smartcard error
+#[maybe_derive_format]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum SmartcardError {
+    /// The ATR is invalid or uses the inverse convention.
+    Atr,
+    /// TCK of the ATR is wrong.
+    Checksum,
+    /// The APDU is invalid.
+    Apdu,
+    /// Unexpected procedure byte from the card.
+    Procedure,
+    /// The response buffer is too small.
+    Buffer,
+    /// The card rejected the byte several times.
+    Nack,
+    Parity,
+    Timeout,
+    Uart(Error),
+}
+
+// ATR ------------------------------------------------------------------------
+
+/// Answer To Reset
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Atr {
+    /// Clock rate conversion integer from TA1
+    pub fi: u8,
+    /// Baud rate adjustment integer from TA1
+    pub di: u8,
+    /// Extra guard time from TC1
+    pub extra_guard_time: u8,
+    /// Work waiting time integer from TC2
+    pub wi: u8,
+    /// Bit `n` is set if the protocol T=n is offered.
+    pub protocols: u16,
+    historical: [u8; ATR_MAX_HISTORICAL_LEN],
+    historical_len: u8,
+}
+
+impl Atr {
+    /// The total length of the ATR, return `None` if more bytes are required.
+    pub fn expected_len(data: &[u8]) -> Option<usize> {
+        let t0 = *data.get(1)?;
+        let mut y = t0 >> 4;
+        // TS, T0
+        let mut n = 2;
+        let mut tck = false;
+        loop {
+            n += y.count_ones() as usize;
+            if y & 0x8 == 0 {
+                break;
+            }
+            let td = *data.get(n - 1)?;
+            tck |= td & 0x0F != 0;
+            y = td >> 4;
+        }
+        Some(n + (t0 & 0x0F) as usize + tck as usize)
+    }
+
+    pub fn parse(data: &[u8]) -> Result<Self, SmartcardError> {
+        if data.first() != Some(&0x3B) {
+            return Err(SmartcardError::Atr);
+        }
+        let len = Self::expected_len(data).ok_or(SmartcardError::Atr)?;
+        if data.len() != len || len > ATR_MAX_LEN {
+            return Err(SmartcardError::Atr);
+        }
+
+        let mut atr = Self {
+            fi: 1,
+            di: 1,
+            extra_guard_time: 0,
+            wi: DEFAULT_WI,
+            protocols: 0,
+            historical: [0; ATR_MAX_HISTORICAL_LEN],
+            historical_len: data[1] & 0x0F,
+        };
+
+        let mut y = data[1] >> 4;
+        let mut i = 2;
+        let mut group = 1;
+        let mut tck = false;
+        loop {
+            // TA, TB, TC, TD
+            let mut bytes = [None; 4];
+            for (bit, b) in bytes.iter_mut().enumerate() {
+                if y & (1 << bit) != 0 {
+                    *b = Some(data[i]);
+                    i += 1;
+                }
+            }
+
+            match (group, bytes) {
+                (1, [ta, _, tc, _]) => {
+                    if let Some(ta) = ta {
+                        atr.fi = ta >> 4;
+                        atr.di = ta & 0x0F;
+                    }
+                    if let Some(tc) = tc {
+                        atr.extra_guard_time = tc;
+                    }
+                }
+                (2, [_, _, Some(tc), _]) => atr.wi = tc,
+                _ => (),
+            }
+
+            match bytes[3] {
+                Some(td) => {
+                    let t = td & 0x0F;
+                    atr.protocols |= 1 << t;
+                    tck |= t != 0;
+                    y = td >> 4;
+                    group += 1;
+                }
+                None => break,
+            }
+        }
+
+        if atr.protocols == 0 {
+            // T=0 is the default protocol
+            atr.protocols = 1;
+        }
+
+        let k = atr.historical_len as usize;
+        atr.historical[..k].copy_from_slice(&data[i..i + k]);
+
+        if tck && data[1..].iter().fold(0, |x, b| x ^ b) != 0 {
+            return Err(SmartcardError::Checksum);
+        }
+        Ok(atr)
+    }
+
+    #[inline]
+    pub fn historical_bytes(&self) -> &[u8] {
+        &self.historical[..self.historical_len as usize]
+    }
+
+    #[inline]
+    pub fn is_protocol_supported(&self, t: u8) -> bool {
+        t < 16 && self.protocols & (1 << t) != 0
+    }
+
+    /// Clock rate conversion factor, return 0 if it's reserved.
+    pub fn f(&self) -> u16 {
+        match self.fi {
+            0 | 1 => 372,
+            2 => 558,
+            3 => 744,
+            4 => 1116,
+            5 => 1488,
+            6 => 1860,
+            9 => 512,
+            10 => 768,
+            11 => 1024,
+            12 => 1536,
+            13 => 2048,
+            _ => 0,
+        }
+    }
+
+    /// Baud rate adjustment factor, return 0 if it's reserved.
+    pub fn d(&self) -> u8 {
+        match self.di {
+            1 => 1,
+            2 => 2,
+            3 => 4,
+            4 => 8,
+            5 => 16,
+            6 => 32,
+            7 => 64,
+            8 => 12,
+            9 => 20,
+            _ => 0,
+        }
+    }
+}
+
+// T=0 ------------------------------------------------------------------------
+
+pub trait SmartcardTransport {
+    /// Send all data to the card.
+    fn send(&mut self, data: &[u8]) -> Result<(), SmartcardError>;
+    /// Block until the buffer is filled.
+    fn recv(&mut self, buf: &mut [u8]) -> Result<(), SmartcardError>;
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct ApduResponse {
+    /// The length of the response data
+    pub len: usize,
+    /// Status word
+    pub sw: u16,
+}
+
+impl ApduResponse {
+    #[inline]
+    pub fn sw1(&self) -> u8 {
+        (self.sw >> 8) as u8
+    }
+
+    #[inline]
+    pub fn sw2(&self) -> u8 {
+        self.sw as u8
+    }
+
+    /// `SW == 0x9000`
+    #[inline]
+    pub fn is_ok(&self) -> bool {
+        self.sw == 0x9000
+    }
+}
+
+/// Exchange a short command APDU with T=0.
+///
+/// - `61xx`: `GET RESPONSE` is sent automatically.
+/// - `6Cxx`: The command is sent again with the right length.
+pub fn t0_transceive<T: SmartcardTransport + ?Sized>(
+    io: &mut T,
+    command: &[u8],
+    response: &mut [u8],
+) -> Result<ApduResponse, SmartcardError> {
+    if command.len() < 4 {
+        return Err(SmartcardError::Apdu);
+    }
+    let mut header = [command[0], command[1], command[2], command[3], 0];
+    let body = &command[4..];
+
+    // (outgoing data, expected length of incoming data)
+    let (data, le) = match body.len() {
+        0 => (&[][..], 0),
+        1 => (&[][..], le_to_len(body[0])),
+        _ => {
+            let lc = body[0] as usize;
+            if lc == 0 || (body.len() != lc + 1 && body.len() != lc + 2) {
+                return Err(SmartcardError::Apdu);
+            }
+            (&body[1..lc + 1], 0)
+        }
+    };
+    header[4] = match data.len() {
+        0 => body.first().copied().unwrap_or(0),
+        n => n as u8,
+    };
+
+    let (mut len, mut sw) = t0_tpdu(io, header, data, le, response)?;
+    if sw >> 8 == 0x6C && data.is_empty() {
+        header[4] = sw as u8;
+        (len, sw) = t0_tpdu(io, header, &[], le_to_len(sw as u8), response)?;
+    }
+
+    while sw >> 8 == 0x61 {
+        let header = [command[0], 0xC0, 0, 0, sw as u8];
+        let buf = response.get_mut(len..).ok_or(SmartcardError::Buffer)?;
+        let (n, s) = t0_tpdu(io, header, &[], le_to_len(sw as u8), buf)?;
+        len += n;
+        sw = s;
+    }
+    Ok(ApduResponse { len, sw })
+}
+
+#[inline]
+fn le_to_len(le: u8) -> usize {
+    if le == 0 { 256 } else { le as usize }
+}
+
+fn t0_tpdu<T: SmartcardTransport + ?Sized>(
+    io: &mut T,
+    header: [u8; 5],
+    data: &[u8],
+    le: usize,
+    response: &mut [u8],
+) -> Result<(usize, u16), SmartcardError> {
+    io.send(&header)?;
+    let ins = header[1];
+    let mut sent = 0;
+    let mut received = 0;
+    loop {
+        let mut pb = [0];
+        io.recv(&mut pb)?;
+        let pb = pb[0];
+        match pb {
+            // NULL, wait
+            0x60 => (),
+            _ if matches!(pb & 0xF0, 0x60 | 0x90) => {
+                let mut sw2 = [0];
+                io.recv(&mut sw2)?;
+                return Ok((received, u16::from_be_bytes([pb, sw2[0]])));
+            }
+            // ACK: all remaining bytes, !ACK: next byte only
+            _ if pb == ins || pb == !ins => {
+                let n = if pb == ins { usize::MAX } else { 1 };
+                if sent < data.len() {
+                    let n = n.min(data.len() - sent);
+                    io.send(&data[sent..sent + n])?;
+                    sent += n;
+                } else if received < le {
+                    let n = n.min(le - received);
+                    let buf = response
+                        .get_mut(received..received + n)
+                        .ok_or(SmartcardError::Buffer)?;
+                    io.recv(buf)?;
+                    received += n;
+                } else {
+                    return Err(SmartcardError::Procedure);
+                }
+            }
+            _ => return Err(SmartcardError::Procedure),
+        }
+    }
+}
+
+// Driver ---------------------------------------------------------------------
+
+pub struct Smartcard<U, OS> {
+    uart: U,
+    byte_timeout: MicrosDurationU32,
+    _os: PhantomData<OS>,
+}
+
+impl<U: UartPeriph, OS: OsInterface> Smartcard<U, OS> {
+    pub fn new(uart: U, baudrate: u32) -> Self {
+        let mut s = Self {
+            uart,
+            byte_timeout: MicrosDurationU32::micros(0),
+            _os: PhantomData,
+        };
+        s.set_waiting_time(baudrate, DEFAULT_WI, 1);
+        s
+    }
+
+    /// Waiting time is `960 * D * WI` etu, and `baudrate` is `1 / etu`.
+    pub fn set_waiting_time(&mut self, baudrate: u32, wi: u8, d: u8) {
+        let etu = 960 * wi as u64 * d as u64;
+        self.byte_timeout = MicrosDurationU32::micros((etu * 1_000_000 / baudrate as u64) as u32);
+    }
+
+    /// Read the answer to reset. Call it right after the reset pin of the card is released.
+    pub fn read_atr(&mut self, buf: &mut [u8; ATR_MAX_LEN]) -> Result<Atr, SmartcardError> {
+        let mut n = 0;
+        loop {
+            if let Some(len) = Atr::expected_len(&buf[..n]) {
+                if len > ATR_MAX_LEN {
+                    return Err(SmartcardError::Atr);
+                }
+                if n == len {
+                    break;
+                }
+            } else if n == ATR_MAX_LEN {
+                return Err(SmartcardError::Atr);
+            }
+            buf[n] = self.read_byte()?;
+            n += 1;
+        }
+        Atr::parse(&buf[..n])
+    }
+
+    pub fn transceive(
+        &mut self,
+        command: &[u8],
+        response: &mut [u8],
+    ) -> Result<ApduResponse, SmartcardError> {
+        t0_transceive(self, command, response)
+    }
+
+    pub fn release(self) -> U {
+        self.uart
+    }
+
+    fn read_byte(&mut self) -> Result<u8, SmartcardError> {
+        let mut t = Timeout::<OS>::micros(self.byte_timeout.to_micros());
+        loop {
+            match self.uart.read() {
+                Ok(data) => return Ok(data as u8),
+                Err(nb::Error::Other(Error::Parity)) => return Err(SmartcardError::Parity),
+                Err(nb::Error::Other(e)) => return Err(SmartcardError::Uart(e)),
+                Err(nb::Error::WouldBlock) => {
+                    if t.timeout() {
+                        return Err(SmartcardError::Timeout);
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl<U: UartPeriph, OS: OsInterface> SmartcardTransport for Smartcard<U, OS> {
+    /// The sent bytes are also received, they are discarded.
+    /// A byte is sent again if the card signals a parity error.
+    fn send(&mut self, data: &[u8]) -> Result<(), SmartcardError> {
+        // Drop the data received before sending
+        while let Ok(_) | Err(nb::Error::Other(_)) = self.uart.read() {}
+
+        for &b in data {
+            let mut retry = 3;
+            loop {
+                let mut t = Timeout::<OS>::micros(self.byte_timeout.to_micros());
+                while self.uart.write(b as u16).is_err() {
+                    if t.timeout() {
+                        return Err(SmartcardError::Timeout);
+                    }
+                }
+
+                match self.read_byte() {
+                    Ok(_) => break,
+                    // NACK causes a framing error
+                    Err(SmartcardError::Uart(Error::FrameFormat)) if retry > 0 => retry -= 1,
+                    Err(SmartcardError::Uart(Error::FrameFormat)) => {
+                        return Err(SmartcardError::Nack);
+                    }
+                    Err(e) => return Err(e),
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn recv(&mut self, buf: &mut [u8]) -> Result<(), SmartcardError> {
+        for b in buf.iter_mut() {
+            *b = self.read_byte()?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn with_tck<const N: usize>(mut atr: [u8; N]) -> [u8; N] {
+        atr[N - 1] = atr[1..N - 1].iter().fold(0, |x, b| x ^ b);
+        atr
+    }
+
+    #[test]
+    fn atr() {
+        let data = [0x3B, 0x02, 0x14, 0x50];
+        for n in 0..2 {
+            assert_eq!(Atr::expected_len(&data[..n]), None);
+        }
+        assert_eq!(Atr::expected_len(&data), Some(4));
+        let atr = Atr::parse(&data).unwrap();
+        assert_eq!(atr.historical_bytes(), &[0x14, 0x50]);
+        assert!(atr.is_protocol_supported(0));
+        assert!(!atr.is_protocol_supported(1));
+        assert_eq!((atr.f(), atr.d(), atr.wi), (372, 1, 10));
+
+        // TC2 for T=0
+        let data = [0x3B, 0x81, 0x40, 0x14, 0x55];
+        assert_eq!(Atr::expected_len(&data[..2]), None);
+        assert_eq!(Atr::expected_len(&data[..3]), Some(5));
+        let atr = Atr::parse(&data).unwrap();
+        assert_eq!(atr.wi, 0x14);
+        assert_eq!(atr.protocols, 1);
+        assert_eq!(atr.historical_bytes(), &[0x55]);
+
+        // TA1, TC1, TD1, TD2, TA3, TB3, T=1 with TCK
+        let data = with_tck([
+            0x3B, 0xD3, 0x96, 0x02, 0x81, 0x31, 0xFE, 0x45, 0x31, 0x80, 0x71, 0,
+        ]);
+        assert_eq!(Atr::expected_len(&data[..4]), None);
+        assert_eq!(Atr::expected_len(&data[..5]), None);
+        assert_eq!(Atr::expected_len(&data[..6]), Some(12));
+        let atr = Atr::parse(&data).unwrap();
+        assert_eq!((atr.f(), atr.d()), (512, 32));
+        assert_eq!(atr.extra_guard_time, 2);
+        assert!(!atr.is_protocol_supported(0));
+        assert!(atr.is_protocol_supported(1));
+        assert_eq!(atr.historical_bytes(), &[0x31, 0x80, 0x71]);
+
+        let mut bad = data;
+        bad[11] ^= 1;
+        assert_eq!(Atr::parse(&bad), Err(SmartcardError::Checksum));
+        assert_eq!(Atr::parse(&data[..11]), Err(SmartcardError::Atr));
+        // Inverse convention
+        assert_eq!(Atr::parse(&[0x3F, 0x00]), Err(SmartcardError::Atr));
+    }
+
+    struct MockCard {
+        rx: &'static [u8],
+        tx: [u8; 64],
+        tx_len: usize,
+    }
+
+    impl MockCard {
+        fn new(rx: &'static [u8]) -> Self {
+            Self {
+                rx,
+                tx: [0; 64],
+                tx_len: 0,
+            }
+        }
+
+        fn sent(&self) -> &[u8] {
+            &self.tx[..self.tx_len]
+        }
+    }
+
+    impl SmartcardTransport for MockCard {
+        fn send(&mut self, data: &[u8]) -> Result<(), SmartcardError> {
+            self.tx[self.tx_len..self.tx_len + data.len()].copy_from_slice(data);
+            self.tx_len += data.len();
+            Ok(())
+        }
+
+        fn recv(&mut self, buf: &mut [u8]) -> Result<(), SmartcardError> {
+            if self.rx.len() < buf.len() {
+                return Err(SmartcardError::Timeout);
+            }
+            let (d, rest) = self.rx.split_at(buf.len());
+            buf.copy_from_slice(d);
+            self.rx = rest;
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn t0_read() {
+        let mut card = MockCard::new(&[0xB0, 1, 2, 3, 4, 0x90, 0x00]);
+        let mut buf = [0; 8];
+        let rsp = t0_transceive(&mut card, &[0x00, 0xB0, 0x00, 0x00, 0x04], &mut buf).unwrap();
+        assert!(rsp.is_ok());
+        assert_eq!(&buf[..rsp.len], &[1, 2, 3, 4]);
+        assert_eq!(card.sent(), &[0x00, 0xB0, 0x00, 0x00, 0x04]);
+
+        // Wrong length
+        let mut card = MockCard::new(&[0x6C, 0x03, 0xB0, 1, 2, 3, 0x90, 0x00]);
+        let rsp = t0_transceive(&mut card, &[0x00, 0xB0, 0x00, 0x00, 0x00], &mut buf).unwrap();
+        assert!(rsp.is_ok());
+        assert_eq!(&buf[..rsp.len], &[1, 2, 3]);
+        assert_eq!(
+            card.sent(),
+            &[0x00, 0xB0, 0x00, 0x00, 0x00, 0x00, 0xB0, 0x00, 0x00, 0x03]
+        );
+
+        // Buffer too small
+        let mut card = MockCard::new(&[0xB0, 1, 2, 3, 4, 0x90, 0x00]);
+        let rst = t0_transceive(&mut card, &[0x00, 0xB0, 0x00, 0x00, 0x04], &mut buf[..2]);
+        assert_eq!(rst, Err(SmartcardError::Buffer));
+    }
+
+    #[test]
+    fn t0_write() {
+        // NULL and single byte procedure
+        let mut card = MockCard::new(&[0x60, 0x29, 0x29, 0x90, 0x00]);
+        let rsp = t0_transceive(
+            &mut card,
+            &[0x00, 0xD6, 0x00, 0x00, 0x02, 0xAA, 0xBB],
+            &mut [],
+        )
+        .unwrap();
+        assert_eq!(rsp, ApduResponse { len: 0, sw: 0x9000 });
+        assert_eq!(card.sent(), &[0x00, 0xD6, 0x00, 0x00, 0x02, 0xAA, 0xBB]);
+
+        // Error status
+        let mut card = MockCard::new(&[0x6A, 0x82]);
+        let rsp = t0_transceive(
+            &mut card,
+            &[0x00, 0xA4, 0x00, 0x00, 0x02, 0x3F, 0x00],
+            &mut [],
+        )
+        .unwrap();
+        assert_eq!((rsp.sw1(), rsp.sw2(), rsp.len), (0x6A, 0x82, 0));
+
+        assert_eq!(
+            t0_transceive(&mut card, &[0x00, 0xA4, 0x00], &mut []),
+            Err(SmartcardError::Apdu)
+        );
+        assert_eq!(
+            t0_transceive(&mut card, &[0x00, 0xA4, 0x00, 0x00, 0x02, 0x3F], &mut []),
+            Err(SmartcardError::Apdu)
+        );
+    }
+
+    #[test]
+    fn t0_get_response() {
+        let mut card = MockCard::new(&[0xA4, 0x61, 0x02, 0xC0, 0x6F, 0x10, 0x90, 0x00]);
+        let mut buf = [0; 4];
+        let rsp = t0_transceive(
+            &mut card,
+            &[0x00, 0xA4, 0x04, 0x00, 0x02, 0x3F, 0x00, 0x00],
+            &mut buf,
+        )
+        .unwrap();
+        assert!(rsp.is_ok());
+        assert_eq!(&buf[..rsp.len], &[0x6F, 0x10]);
+        assert_eq!(
+            card.sent(),
+            &[
+                0x00, 0xA4, 0x04, 0x00, 0x02, 0x3F, 0x00, 0x00, 0xC0, 0x00, 0x00, 0x02
+            ]
+        );
+
+        // Unexpected procedure byte
+        let mut card = MockCard::new(&[0x12]);
+        assert_eq!(
+            t0_transceive(&mut card, &[0x00, 0xB0, 0x00, 0x00, 0x04], &mut buf),
+            Err(SmartcardError::Procedure)
+        );
+    }
+}
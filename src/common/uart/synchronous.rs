@@ -0,0 +1,171 @@
+//! Synchronous master mode, the UART outputs the clock on the CK pin.
+//!
+//! It works like a SPI master without NSS: TX is MOSI, RX is MISO and CK is SCK.
+//! The data is shifted LSB first by the hardware, MSB first is done in software.
+
+use super::*;
+use crate::common::{
+    embedded_hal::spi::{self, Mode},
+    os_trait::Timeout,
+    spi::Error as SpiError,
+};
+use core::marker::PhantomData;
+
+pub struct SyncConfig {
+    pub mode: Mode,
+    /// Output the clock pulse of the last data bit
+    pub last_bit_clock: bool,
+    pub msb_first: bool,
+}
+
+impl Default for SyncConfig {
+    fn default() -> Self {
+        Self {
+            mode: spi::MODE_0,
+            last_bit_clock: true,
+            msb_first: true,
+        }
+    }
+}
+
+impl SyncConfig {
+    pub fn mode(mut self, mode: Mode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    pub fn last_bit_clock(mut self, enable: bool) -> Self {
+        self.last_bit_clock = enable;
+        self
+    }
+
+    pub fn msb_first(mut self, msb_first: bool) -> Self {
+        self.msb_first = msb_first;
+        self
+    }
+}
+
+pub struct UartSyncMaster<U, OS> {
+    uart: U,
+    rx: bool,
+    msb_first: bool,
+    timeout: MicrosDurationU32,
+    _os: PhantomData<OS>,
+}
+
+impl<U: UartPeriph, OS: OsInterface> UartSyncMaster<U, OS> {
+    /// `rx`: whether the receiver is enabled. If not, zeros are read.
+    pub fn new(uart: U, baudrate: u32, rx: bool, msb_first: bool) -> Self {
+        Self {
+            uart,
+            rx,
+            msb_first,
+            timeout: calculate_timeout(baudrate, 4),
+            _os: PhantomData,
+        }
+    }
+
+    fn transfer_word(&mut self, word: u8) -> Result<u8, SpiError> {
+        let word = if self.msb_first {
+            word.reverse_bits()
+        } else {
+            word
+        };
+
+        let mut t = Timeout::<OS>::micros(self.timeout.to_micros());
+        while self.uart.write(word as u16).is_err() {
+            if t.timeout() {
+                return Err(SpiError::Timeout);
+            }
+        }
+
+        if !self.rx {
+            return Ok(0);
+        }
+
+        t.restart();
+        loop {
+            match self.uart.read() {
+                Ok(data) => {
+                    let data = data as u8;
+                    return Ok(if self.msb_first {
+                        data.reverse_bits()
+                    } else {
+                        data
+                    });
+                }
+                Err(nb::Error::Other(Error::Overrun)) => return Err(SpiError::Overrun),
+                Err(nb::Error::Other(_)) => return Err(SpiError::Other),
+                Err(nb::Error::WouldBlock) => {
+                    if t.timeout() {
+                        return Err(SpiError::Timeout);
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn release(self) -> U {
+        self.uart
+    }
+}
+
+impl<U: UartPeriph, OS: OsInterface> UartReconfigure for UartSyncMaster<U, OS> {
+    /// Every transfer is complete before it returns.
+    #[inline]
+    fn suspend(&mut self, _timeout: MicrosDurationU32) -> Result<(), Error> {
+        Ok(())
+    }
+
+    #[inline]
+    fn resume(&mut self, baudrate: u32) {
+        self.timeout = calculate_timeout(baudrate, 4);
+    }
+}
+
+impl<U: UartPeriph, OS: OsInterface> spi::ErrorType for UartSyncMaster<U, OS> {
+    type Error = SpiError;
+}
+
+impl<U: UartPeriph, OS: OsInterface> spi::SpiBus<u8> for UartSyncMaster<U, OS> {
+    fn read(&mut self, words: &mut [u8]) -> Result<(), Self::Error> {
+        for w in words.iter_mut() {
+            *w = self.transfer_word(0)?;
+        }
+        Ok(())
+    }
+
+    fn write(&mut self, words: &[u8]) -> Result<(), Self::Error> {
+        for &w in words {
+            self.transfer_word(w)?;
+        }
+        Ok(())
+    }
+
+    fn transfer(&mut self, read: &mut [u8], write: &[u8]) -> Result<(), Self::Error> {
+        for i in 0..read.len().max(write.len()) {
+            let data = self.transfer_word(write.get(i).copied().unwrap_or(0))?;
+            if let Some(r) = read.get_mut(i) {
+                *r = data;
+            }
+        }
+        Ok(())
+    }
+
+    fn transfer_in_place(&mut self, words: &mut [u8]) -> Result<(), Self::Error> {
+        for w in words.iter_mut() {
+            *w = self.transfer_word(*w)?;
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        let mut t = Timeout::<OS>::micros(self.timeout.to_micros());
+        while !self.uart.is_tx_complete() {
+            if t.timeout() {
+                return Err(SpiError::Timeout);
+            }
+        }
+        Ok(())
+    }
+}
@@ -3,16 +3,21 @@ use crate::{
     Steal,
     common::{
         dma::*,
+        embedded_dma::ReadBuffer,
         embedded_io::{BufRead, ErrorType, Read, ReadReady, Write, WriteReady},
         os_trait::Duration,
+        ringbuf::*,
     },
+    l,
 };
 
 // TX -------------------------------------------------------------------------
 
 pub struct UartDmaBufTx<U, CH, OS: OsInterface> {
-    _uart: U,
+    uart: U,
     w: DmaRingbufTxWriter<u8, CH>,
+    buf_size: usize,
+    baudrate: u32,
     timeout: MicrosDurationU32,
     flush_timeout: MicrosDurationU32,
     waiter: OS::NotifyWaiter,
@@ -37,8 +42,10 @@ where
         let (w, l) = DmaRingbufTx::new(dma_ch, uart.get_tx_data_reg_addr(), buf_size, notifier);
         (
             Self {
-                _uart: uart,
+                uart,
                 w,
+                buf_size,
+                baudrate,
                 timeout,
                 flush_timeout: calculate_timeout(baudrate, buf_size + 10),
                 waiter,
@@ -46,6 +53,147 @@ where
             l,
         )
     }
+
+    /// Update the timeouts after the baud rate is changed.
+    pub fn set_baudrate(&mut self, baudrate: u32) {
+        self.baudrate = baudrate;
+        self.flush_timeout = calculate_timeout(baudrate, self.buf_size + 10);
+    }
+
+    /// The time to wait for free space in a write, zero makes it non-blocking.
+    /// Returns [`Error::Timeout`] if nothing is written.
+    pub fn set_timeout(&mut self, timeout: MicrosDurationU32) {
+        self.timeout = timeout;
+    }
+
+    /// Send a break character after the current character.
+    /// Flush it first to send the break after all the buffered data.
+    #[inline]
+    pub fn send_break(&mut self) {
+        self.uart.send_break();
+    }
+}
+
+impl<U, CH, OS> UartDmaBufTx<U, CH, OS>
+where
+    CH: DmaChannel,
+    OS: OsInterface,
+{
+    /// Same as [`Write::flush`], but it waits for `timeout` instead of
+    /// the time needed to send the whole buffer.
+    pub fn flush_with_timeout(&mut self, timeout: MicrosDurationU32) -> Result<(), Error> {
+        self.waiter
+            .wait_with(&Duration::<OS>::micros(timeout.ticks()), || {
+                if self.w.is_empty() && !self.w.in_progress() {
+                    Some(())
+                } else {
+                    None
+                }
+            })
+            .ok_or(Error::Timeout)
+    }
+}
+
+impl<U, CH, OS> UartDmaBufTx<U, CH, OS>
+where
+    U: UartPeriphWithDma,
+    CH: DmaChannel + Steal,
+    OS: OsInterface,
+{
+    /// Send `buf` by DMA directly, without copying it into the ring buffer.
+    /// The buffered data are flushed first, and the ring buffer is used again
+    /// after the returned transfer is finished or dropped.
+    ///
+    /// The length of `buf` must be 1 to 65535. It panics in debug builds if it's not,
+    /// and [`Error::Other`] is returned in release builds.
+    pub fn write_zero_copy<BUF>(
+        &mut self,
+        buf: BUF,
+    ) -> Result<UartDmaTxTransfer<'_, BUF, U, CH, OS>, Error>
+    where
+        BUF: ReadBuffer<Word = u8> + 'static,
+    {
+        let (_, len) = unsafe { buf.read_buffer() };
+        let valid = len > 0 && len <= u16::MAX as usize;
+        l::debug_assert!(valid, "invalid zero-copy buffer length: {}", len);
+        if !valid {
+            return Err(Error::Other);
+        }
+
+        self.flush()?;
+        self.w.cancel();
+        let ch = unsafe { self.w.steal_channel() };
+        let transfer = Transfer::write(ch, self.uart.get_tx_data_reg_addr(), buf);
+        let timeout = calculate_timeout(self.baudrate, len + 10);
+        Ok(UartDmaTxTransfer {
+            transfer,
+            tx: self,
+            timeout,
+        })
+    }
+}
+
+/// A transmission started by [`UartDmaBufTx::write_zero_copy`].
+/// It's aborted when it's dropped.
+pub struct UartDmaTxTransfer<'a, BUF, U, CH: DmaChannel, OS: OsInterface> {
+    transfer: Transfer<BUF, CH>,
+    tx: &'a mut UartDmaBufTx<U, CH, OS>,
+    timeout: MicrosDurationU32,
+}
+
+impl<BUF, U, CH, OS> UartDmaTxTransfer<'_, BUF, U, CH, OS>
+where
+    CH: DmaChannel,
+    OS: OsInterface,
+{
+    /// All the data are loaded into the UART, the last bytes may be still being sent.
+    #[inline]
+    pub fn is_done(&self) -> bool {
+        self.transfer.is_done()
+    }
+
+    /// The number of bytes that haven't been loaded into the UART
+    #[inline]
+    pub fn get_unprocessed_len(&self) -> usize {
+        self.transfer.get_unprocessed_len()
+    }
+
+    /// Wait until the transfer is done and get the buffer back.
+    /// Returns itself on timeout, it can be waited again or aborted.
+    pub fn wait(self) -> Result<BUF, Self> {
+        let done = self
+            .tx
+            .waiter
+            .wait_with(&Duration::<OS>::micros(self.timeout.ticks()), || {
+                self.transfer.is_done().then_some(())
+            });
+        match done {
+            Some(()) => Ok(self.transfer.wait().0),
+            None => Err(self),
+        }
+    }
+
+    /// Stop the transfer even if it's in progress, and get the buffer back.
+    pub fn abort(self) -> BUF {
+        self.transfer.abort().0
+    }
+}
+
+impl<U, CH, OS> UartReconfigure for UartDmaBufTx<U, CH, OS>
+where
+    U: UartPeriphWithDma,
+    CH: DmaChannel,
+    OS: OsInterface,
+{
+    #[inline]
+    fn suspend(&mut self, timeout: MicrosDurationU32) -> Result<(), Error> {
+        self.flush_with_timeout(timeout)
+    }
+
+    #[inline]
+    fn resume(&mut self, baudrate: u32) {
+        self.set_baudrate(baudrate);
+    }
 }
 
 impl<U, CH, OS> ErrorType for UartDmaBufTx<U, CH, OS>
@@ -72,22 +220,15 @@ where
             if let n @ 1.. = self.w.write(buf) {
                 return Ok(n);
             } else if timeout {
-                return Err(Error::Busy);
+                return Err(Error::Timeout);
             }
             timeout = !self.waiter.wait(&dur);
         }
     }
 
+    #[inline]
     fn flush(&mut self) -> Result<(), Self::Error> {
-        self.waiter
-            .wait_with(&Duration::<OS>::micros(self.flush_timeout.ticks()), || {
-                if self.w.is_empty() && !self.w.in_progress() {
-                    Some(())
-                } else {
-                    None
-                }
-            })
-            .ok_or(Error::Other)
+        self.flush_with_timeout(self.flush_timeout)
     }
 }
 
@@ -104,10 +245,11 @@ where
 // RX -------------------------------------------------------------------------
 
 pub struct UartDmaRx<U, CH, OS: OsInterface> {
-    _uart: U,
+    uart: U,
     ch: DmaCircularBufferRx<u8, CH>,
     timeout: MicrosDurationU32,
     waiter: OS::NotifyWaiter,
+    err: Arc<RxErrorState>,
 }
 
 impl<U, CH, OS> UartDmaRx<U, CH, OS>
@@ -127,44 +269,72 @@ where
         let ch = DmaCircularBufferRx::<u8, CH>::new(dma_ch2, uart.get_rx_data_reg_addr(), buf_size);
         uart.enable_dma_rx(true);
         uart.set_interrupt(Event::Idle, true);
+        uart.set_interrupt(Event::RxError, true);
         dma_ch.set_interrupt(DmaEvent::HalfTransfer, true);
         dma_ch.set_interrupt(DmaEvent::TransferComplete, true);
         let uart2 = unsafe { uart.steal() };
+        let err = Arc::new(RxErrorState::new());
+        let laps = ch.get_laps();
         (
             Self {
-                _uart: uart2,
+                uart: uart2,
                 ch,
                 timeout,
                 waiter,
+                err: Arc::clone(&err),
             },
             UartDmaRxNotify {
                 notifier: notifier.clone(),
                 ch: dma_ch,
+                laps,
+            },
+            UartIdleNotify {
+                uart,
+                notifier,
+                err,
             },
-            UartIdleNotify { uart, notifier },
         )
     }
 }
 
-impl<U, CH, OS> ErrorType for UartDmaRx<U, CH, OS>
-where
-    OS: OsInterface,
-{
-    type Error = Error;
-}
-
-impl<U, CH, OS> Read for UartDmaRx<U, CH, OS>
+impl<U, CH, OS> UartDmaRx<U, CH, OS>
 where
+    U: UartPeriph,
     CH: DmaChannel,
     OS: OsInterface,
 {
-    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+    pub fn error_stats(&self) -> UartErrorStats {
+        self.err.stats()
+    }
+
+    pub fn reset_error_stats(&mut self) {
+        self.err.reset_stats();
+    }
+
+    /// What to do when the unread data are overwritten, the default is [`OverrunPolicy::DropOldest`].
+    pub fn set_overrun_policy(&mut self, policy: OverrunPolicy) {
+        self.ch.set_overrun_policy(policy);
+    }
+
+    /// The time to wait for the first byte in a read, zero makes it non-blocking.
+    pub fn set_timeout(&mut self, timeout: MicrosDurationU32) {
+        self.timeout = timeout;
+    }
+
+    /// Same as [`Read::read`], but it waits for `timeout` instead of the default.
+    /// Returns [`Error::Timeout`] if no byte is received.
+    pub fn read_with_timeout(
+        &mut self,
+        buf: &mut [u8],
+        timeout: MicrosDurationU32,
+    ) -> Result<usize, Error> {
         if buf.is_empty() {
             return Err(Error::Other);
         }
+        self.check_error()?;
 
         self.waiter
-            .wait_with(&Duration::<OS>::micros(self.timeout.ticks()), || {
+            .wait_with(&Duration::<OS>::micros(timeout.ticks()), || {
                 if let Some(d) = self.ch.read_slice(buf.len()) {
                     buf[..d.len()].copy_from_slice(d);
                     self.ch.consume(d.len());
@@ -173,21 +343,77 @@ where
                     None
                 }
             })
-            .ok_or(Error::Other)
+            .ok_or(Error::Timeout)
+    }
+
+    /// The buffer overrun and the receive errors are returned from the next read.
+    /// The position of a receive error is unknown in DMA mode.
+    fn check_error(&mut self) -> Result<(), Error> {
+        if self.ch.check_overrun() {
+            return Err(Error::Overrun);
+        }
+
+        let rst = self.err.check(0, false).map(|_| ());
+        // It's disabled by the interrupt handler to avoid repeated interrupts
+        if !self.uart.is_interrupt_enable(Event::RxError) {
+            self.uart.set_interrupt(Event::RxError, true);
+        }
+        rst
+    }
+}
+
+/// The DMA requests are disabled while it's suspended, so the data register can be cleared.
+/// Its timeout doesn't depend on the baud rate.
+impl<U, CH, OS> UartReconfigure for UartDmaRx<U, CH, OS>
+where
+    U: UartPeriphWithDma,
+    CH: DmaChannel,
+    OS: OsInterface,
+{
+    fn suspend(&mut self, _timeout: MicrosDurationU32) -> Result<(), Error> {
+        self.uart.enable_dma_rx(false);
+        Ok(())
+    }
+
+    fn resume(&mut self, _baudrate: u32) {
+        self.ch.discard();
+        self.err.clear();
+        self.uart.enable_dma_rx(true);
+    }
+}
+
+impl<U, CH, OS> ErrorType for UartDmaRx<U, CH, OS>
+where
+    OS: OsInterface,
+{
+    type Error = Error;
+}
+
+impl<U, CH, OS> Read for UartDmaRx<U, CH, OS>
+where
+    U: UartPeriph,
+    CH: DmaChannel,
+    OS: OsInterface,
+{
+    #[inline]
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        self.read_with_timeout(buf, self.timeout)
     }
 }
 
 impl<U, CH, OS> BufRead for UartDmaRx<U, CH, OS>
 where
+    U: UartPeriph,
     CH: DmaChannel,
     OS: OsInterface,
 {
     fn fill_buf(&mut self) -> Result<&[u8], Self::Error> {
+        self.check_error()?;
         self.waiter
             .wait_with(&Duration::<OS>::micros(self.timeout.ticks()), || {
                 self.ch.read_slice(usize::MAX)
             })
-            .ok_or(Error::Other)
+            .ok_or(Error::Timeout)
     }
 
     fn consume(&mut self, amt: usize) {
@@ -205,9 +431,41 @@ where
     }
 }
 
+impl<U, CH, OS> UartBufferedRx for UartDmaRx<U, CH, OS>
+where
+    U: UartPeriph,
+    CH: DmaChannel,
+    OS: OsInterface,
+{
+    #[inline]
+    fn set_timeout(&mut self, timeout: MicrosDurationU32) {
+        self.set_timeout(timeout);
+    }
+
+    #[inline]
+    fn read_with_timeout(
+        &mut self,
+        buf: &mut [u8],
+        timeout: MicrosDurationU32,
+    ) -> Result<usize, Error> {
+        self.read_with_timeout(buf, timeout)
+    }
+
+    #[inline]
+    fn error_stats(&self) -> UartErrorStats {
+        self.error_stats()
+    }
+
+    #[inline]
+    fn reset_error_stats(&mut self) {
+        self.reset_error_stats();
+    }
+}
+
 pub struct UartDmaRxNotify<CH, OS: OsInterface> {
     notifier: OS::Notifier,
     ch: CH,
+    laps: DmaRxLaps,
 }
 
 impl<CH, OS> UartDmaRxNotify<CH, OS>
@@ -216,11 +474,17 @@ where
     OS: OsInterface,
 {
     pub fn interrupt_notify(&mut self) {
-        if self.ch.check_and_clear_interrupt(DmaEvent::HalfTransfer)
-            || self
-                .ch
-                .check_and_clear_interrupt(DmaEvent::TransferComplete)
-        {
+        let half = self.ch.check_and_clear_interrupt(DmaEvent::HalfTransfer);
+        let complete = self
+            .ch
+            .check_and_clear_interrupt(DmaEvent::TransferComplete);
+        if half {
+            self.laps.half();
+        }
+        if complete {
+            self.laps.complete();
+        }
+        if half || complete {
             self.notifier.notify();
         }
     }
@@ -229,6 +493,7 @@ where
 pub struct UartIdleNotify<U, OS: OsInterface> {
     uart: U,
     notifier: OS::Notifier,
+    err: Arc<RxErrorState>,
 }
 
 impl<U, OS> UartIdleNotify<U, OS>
@@ -236,9 +501,278 @@ where
     U: UartPeriph,
     OS: OsInterface,
 {
+    /// Call it in the UART interrupt callback, it also captures the receive errors.
     pub fn interrupt_notify(&mut self) {
-        if self.uart.check_and_clear_interrupt(Event::Idle) {
+        if self.check_idle() {
             self.notifier.notify();
         }
     }
+
+    /// Returns `true` if the line is idle.
+    fn check_idle(&mut self) -> bool {
+        let status = self.uart.get_status();
+        if status.lin_break {
+            self.uart.check_and_clear_interrupt(Event::LinBreak);
+        }
+        if status.idle {
+            self.uart.check_and_clear_interrupt(Event::Idle);
+        }
+        self.process_status(&status)
+    }
+
+    /// Returns `true` if the line is idle, the flags are not cleared.
+    /// A break can only be detected in LIN mode, otherwise it's a framing error.
+    fn process_status(&mut self, status: &UartStatus) -> bool {
+        if status.lin_break {
+            self.err.record(Error::Break, 0);
+            self.notifier.notify();
+        }
+
+        match status.rx_error {
+            Some(e) if self.uart.is_interrupt_enable(Event::RxError) => {
+                self.err.record(e, 0);
+                // The flags are cleared by the next DMA read of the data register
+                self.uart.set_interrupt(Event::RxError, false);
+                self.notifier.notify();
+            }
+            _ => (),
+        }
+
+        status.idle
+    }
+}
+
+impl<U, OS> UartEventHandler for UartIdleNotify<U, OS>
+where
+    U: UartPeriph,
+    OS: OsInterface,
+{
+    #[inline]
+    fn on_event(&mut self, status: &UartStatus) {
+        if self.process_status(status) {
+            self.notifier.notify();
+        }
+    }
+}
+
+// Packet RX ------------------------------------------------------------------
+
+/// A frame received before an idle line.
+/// It may span the end of the circular buffer, so it consists of two slices.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UartPacket<'a> {
+    pub first: &'a [u8],
+    pub second: &'a [u8],
+}
+
+impl UartPacket<'_> {
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.first.len() + self.second.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the number of bytes copied, the rest that doesn't fit in `buf` is dropped.
+    pub fn copy_to(&self, buf: &mut [u8]) -> usize {
+        let n1 = self.first.len().min(buf.len());
+        buf[..n1].copy_from_slice(&self.first[..n1]);
+        let n2 = self.second.len().min(buf.len() - n1);
+        buf[n1..n1 + n2].copy_from_slice(&self.second[..n2]);
+        n1 + n2
+    }
+}
+
+/// Receive data by DMA and split them into frames at idle lines.
+pub struct UartDmaPacketRx<U, CH, OS: OsInterface> {
+    rx: UartDmaRx<U, CH, OS>,
+    /// The DMA remaining length at each idle line
+    r: Consumer<usize>,
+}
+
+impl<U, CH, OS> UartDmaPacketRx<U, CH, OS>
+where
+    U: UartPeriphWithDma + Steal,
+    CH: DmaChannel + Steal,
+    OS: OsInterface,
+{
+    /// `queue_size`: the maximum number of frames that are received but not read.
+    pub fn new(
+        uart: U,
+        dma_ch: CH,
+        buf_size: usize,
+        queue_size: usize,
+        timeout: MicrosDurationU32,
+    ) -> (
+        Self,
+        UartDmaRxNotify<CH, OS>,
+        UartPacketIdleNotify<U, CH, OS>,
+    ) {
+        let ch = unsafe { dma_ch.steal() };
+        let (mut rx, notify, idle) = UartDmaRx::new(uart, dma_ch, buf_size, timeout);
+        // The frame boundaries are lost after an overrun
+        rx.set_overrun_policy(OverrunPolicy::Reset);
+        let (w, r) = RingBuffer::<usize>::new(queue_size);
+        (Self { rx, r }, notify, UartPacketIdleNotify { idle, ch, w })
+    }
+}
+
+impl<U, CH, OS> UartDmaPacketRx<U, CH, OS>
+where
+    U: UartPeriph,
+    CH: DmaChannel,
+    OS: OsInterface,
+{
+    /// Wait for the next frame. The frame is in the DMA buffer, so it must be processed
+    /// before the buffer is filled again.
+    ///
+    /// All the unread frames are dropped on [`Error::Overrun`].
+    #[inline]
+    pub fn wait_packet(&mut self) -> Result<UartPacket<'_>, Error> {
+        self.wait_packet_with_timeout(self.rx.timeout)
+    }
+
+    /// Same as [`UartDmaPacketRx::wait_packet`], but it waits for `timeout` instead of the default.
+    /// Returns [`Error::Timeout`] if no frame is received.
+    pub fn wait_packet_with_timeout(
+        &mut self,
+        timeout: MicrosDurationU32,
+    ) -> Result<UartPacket<'_>, Error> {
+        if let Err(e) = self.rx.check_error() {
+            if e == Error::Overrun {
+                while self.r.pop().is_ok() {}
+            }
+            return Err(e);
+        }
+
+        let ch = &self.rx.ch;
+        let r = &mut self.r;
+        let end = self
+            .rx
+            .waiter
+            .wait_with(&Duration::<OS>::micros(timeout.ticks()), || {
+                // Skip the empty frames, e.g. an idle line after the data are already read
+                while let Ok(end) = r.pop() {
+                    if ch.has_data_until(end) {
+                        return Some(end);
+                    }
+                }
+                None
+            })
+            .ok_or(Error::Timeout)?;
+        let (first, second) = self.rx.ch.read_until(end);
+        Ok(UartPacket { first, second })
+    }
+
+    /// Same as [`UartDmaPacketRx::wait_packet`], but the frame is copied into `buf`.
+    /// Returns the number of bytes copied, the rest that doesn't fit in `buf` is dropped.
+    pub fn read_packet(&mut self, buf: &mut [u8]) -> Result<usize, Error> {
+        Ok(self.wait_packet()?.copy_to(buf))
+    }
+
+    pub fn error_stats(&self) -> UartErrorStats {
+        self.rx.error_stats()
+    }
+
+    pub fn reset_error_stats(&mut self) {
+        self.rx.reset_error_stats();
+    }
+
+    /// The time to wait for a frame, zero makes it non-blocking.
+    pub fn set_timeout(&mut self, timeout: MicrosDurationU32) {
+        self.rx.set_timeout(timeout);
+    }
+}
+
+impl<U, CH, OS> UartReconfigure for UartDmaPacketRx<U, CH, OS>
+where
+    U: UartPeriphWithDma,
+    CH: DmaChannel,
+    OS: OsInterface,
+{
+    #[inline]
+    fn suspend(&mut self, timeout: MicrosDurationU32) -> Result<(), Error> {
+        self.rx.suspend(timeout)
+    }
+
+    fn resume(&mut self, baudrate: u32) {
+        while self.r.pop().is_ok() {}
+        self.rx.resume(baudrate);
+    }
+}
+
+pub struct UartPacketIdleNotify<U, CH, OS: OsInterface> {
+    idle: UartIdleNotify<U, OS>,
+    ch: CH,
+    w: Producer<usize>,
+}
+
+impl<U, CH, OS> UartPacketIdleNotify<U, CH, OS>
+where
+    U: UartPeriph,
+    CH: DmaChannel,
+    OS: OsInterface,
+{
+    /// Call it in the UART interrupt callback, it records the end of the frame.
+    pub fn interrupt_notify(&mut self) {
+        if self.idle.check_idle() {
+            self.end_packet();
+        }
+    }
+
+    fn end_packet(&mut self) {
+        // If the queue is full, the frame is merged into the next one
+        self.w.push(self.ch.get_unprocessed_len()).ok();
+        self.idle.notifier.notify();
+    }
+}
+
+impl<U, CH, OS> UartEventHandler for UartPacketIdleNotify<U, CH, OS>
+where
+    U: UartPeriph,
+    CH: DmaChannel,
+    OS: OsInterface,
+{
+    #[inline]
+    fn on_event(&mut self, status: &UartStatus) {
+        if self.idle.process_status(status) {
+            self.end_packet();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::{
+        dma::mock::MockDma, fugit::ExtU32, os_trait::FakeOs, uart::mock::MockUart,
+    };
+
+    #[test]
+    fn packet() {
+        let u = MockUart::default();
+        let dma = MockDma::default();
+        let (mut rx, _notify, mut idle) =
+            UartDmaPacketRx::<_, _, FakeOs>::new(u.clone(), dma.clone(), 16, 4, 0.micros());
+        let mut buf = [0; 8];
+
+        dma.receive(&[1, 2, 3], None);
+        u.0.borrow_mut().idle = true;
+        idle.interrupt_notify();
+        // An idle line without new data
+        u.0.borrow_mut().idle = true;
+        idle.interrupt_notify();
+        assert_eq!(rx.read_packet(&mut buf), Ok(3));
+        assert_eq!(buf[..3], [1, 2, 3]);
+        assert_eq!(rx.read_packet(&mut buf), Err(Error::Timeout));
+
+        dma.receive(&[4, 5], None);
+        u.0.borrow_mut().idle = true;
+        idle.interrupt_notify();
+        assert_eq!(rx.read_packet(&mut buf), Ok(2));
+        assert_eq!(buf[..2], [4, 5]);
+    }
 }
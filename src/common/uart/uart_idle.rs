@@ -0,0 +1,351 @@
+//! Buffered receiver driven by the RXNE and IDLE interrupts.
+//!
+//! It works like [`UartDmaRx`] for the UARTs without DMA, such as UART5.
+//! The reader is woken at an idle line or when the buffer is nearly full.
+
+use super::*;
+use crate::common::{
+    embedded_io::{BufRead, ErrorType, Read, ReadReady},
+    os_trait::Duration,
+    ringbuf::*,
+};
+
+/// A buffered receiver that wakes the reader at an idle line.
+/// It's implemented by [`UartDmaRx`] and [`UartIdleRx`], so all the UARTs can be used in the same way.
+pub trait UartBufferedRx: Read + BufRead + ReadReady + ErrorType<Error = Error> {
+    /// The time to wait for the first byte in a read, zero makes it non-blocking.
+    fn set_timeout(&mut self, timeout: MicrosDurationU32);
+    /// Same as [`Read::read`], but it waits for `timeout` instead of the default.
+    fn read_with_timeout(
+        &mut self,
+        buf: &mut [u8],
+        timeout: MicrosDurationU32,
+    ) -> Result<usize, Error>;
+    fn error_stats(&self) -> UartErrorStats;
+    fn reset_error_stats(&mut self);
+}
+
+// RX -------------------------------------------------------------------------
+
+pub struct UartIdleRx<U, OS: OsInterface> {
+    uart: U,
+    timeout: MicrosDurationU32,
+    r: Consumer<u8>,
+    waiter: OS::NotifyWaiter,
+    err: Arc<RxErrorState>,
+    read_count: usize,
+    mark_error_position: bool,
+}
+
+impl<U, OS> UartIdleRx<U, OS>
+where
+    U: UartPeriph,
+    OS: OsInterface,
+{
+    pub fn new(
+        uart: [U; 2],
+        buf_size: usize,
+        timeout: MicrosDurationU32,
+    ) -> (Self, UartIdleRxHandler<U, OS>) {
+        let (notifier, waiter) = OS::notify();
+        let [uart, mut u2] = uart;
+        let (w, r) = RingBuffer::<u8>::new(buf_size);
+        let err = Arc::new(RxErrorState::new());
+        u2.set_interrupt(Event::RxNotEmpty, true);
+        u2.set_interrupt(Event::Idle, true);
+        (
+            Self {
+                uart,
+                timeout,
+                r,
+                waiter,
+                err: Arc::clone(&err),
+                read_count: 0,
+                mark_error_position: false,
+            },
+            UartIdleRxHandler {
+                uart: u2,
+                w,
+                notifier,
+                err,
+                recv_count: 0,
+            },
+        )
+    }
+
+    /// Same as [`UartInterruptRx::mark_error_position`].
+    /// The data dropped on a full buffer are returned as [`Error::Overrun`],
+    /// and they are counted in the overrun errors.
+    pub fn mark_error_position(&mut self, enable: bool) {
+        self.mark_error_position = enable;
+    }
+
+    pub fn error_stats(&self) -> UartErrorStats {
+        self.err.stats()
+    }
+
+    pub fn reset_error_stats(&mut self) {
+        self.err.reset_stats();
+    }
+
+    /// The time to wait for the first byte in a read, zero makes it non-blocking.
+    pub fn set_timeout(&mut self, timeout: MicrosDurationU32) {
+        self.timeout = timeout;
+    }
+
+    /// Same as [`Read::read`], but it waits for `timeout` instead of the default.
+    /// Returns [`Error::Timeout`] if no byte is received.
+    pub fn read_with_timeout(
+        &mut self,
+        buf: &mut [u8],
+        timeout: MicrosDurationU32,
+    ) -> Result<usize, Error> {
+        if buf.is_empty() {
+            return Err(Error::Other);
+        }
+
+        let max = self.err.check(self.read_count, self.mark_error_position)?;
+        let len = max.min(buf.len());
+        let buf = &mut buf[..len];
+        let n = self
+            .waiter
+            .wait_with(&Duration::<OS>::micros(timeout.ticks()), || {
+                if let n @ 1.. = self.r.pop_slice(buf) {
+                    return Some(n);
+                } else if !self.uart.is_interrupt_enable(Event::RxNotEmpty) {
+                    self.uart.set_interrupt(Event::RxNotEmpty, true);
+                }
+                None
+            })
+            .ok_or(Error::Timeout)?;
+        self.read_count = self.read_count.wrapping_add(n);
+        Ok(n)
+    }
+}
+
+impl<U, OS> ErrorType for UartIdleRx<U, OS>
+where
+    OS: OsInterface,
+{
+    type Error = Error;
+}
+
+impl<U, OS> Read for UartIdleRx<U, OS>
+where
+    U: UartPeriph,
+    OS: OsInterface,
+{
+    #[inline]
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        self.read_with_timeout(buf, self.timeout)
+    }
+}
+
+impl<U, OS> BufRead for UartIdleRx<U, OS>
+where
+    U: UartPeriph,
+    OS: OsInterface,
+{
+    fn fill_buf(&mut self) -> Result<&[u8], Self::Error> {
+        let max = self.err.check(self.read_count, self.mark_error_position)?;
+        self.waiter
+            .wait_with(&Duration::<OS>::micros(self.timeout.ticks()), || {
+                if let Some(chunk) = self.r.get_read_chunk() {
+                    let buf = chunk.get_slice();
+                    let p = buf.as_ptr();
+                    let len = buf.len().min(max);
+                    return unsafe { Some(core::slice::from_raw_parts(p, len)) };
+                } else if !self.uart.is_interrupt_enable(Event::RxNotEmpty) {
+                    self.uart.set_interrupt(Event::RxNotEmpty, true);
+                }
+                None
+            })
+            .ok_or(Error::Timeout)
+    }
+
+    fn consume(&mut self, amt: usize) {
+        if let Ok(chunk) = self.r.read_chunk(amt) {
+            chunk.commit_all();
+            self.read_count = self.read_count.wrapping_add(amt);
+        }
+    }
+}
+
+impl<U, OS> ReadReady for UartIdleRx<U, OS>
+where
+    U: UartPeriph,
+    OS: OsInterface,
+{
+    fn read_ready(&mut self) -> Result<bool, Self::Error> {
+        Ok(self.r.peek().is_ok())
+    }
+}
+
+impl<U, OS> UartBufferedRx for UartIdleRx<U, OS>
+where
+    U: UartPeriph,
+    OS: OsInterface,
+{
+    #[inline]
+    fn set_timeout(&mut self, timeout: MicrosDurationU32) {
+        self.set_timeout(timeout);
+    }
+
+    #[inline]
+    fn read_with_timeout(
+        &mut self,
+        buf: &mut [u8],
+        timeout: MicrosDurationU32,
+    ) -> Result<usize, Error> {
+        self.read_with_timeout(buf, timeout)
+    }
+
+    #[inline]
+    fn error_stats(&self) -> UartErrorStats {
+        self.error_stats()
+    }
+
+    #[inline]
+    fn reset_error_stats(&mut self) {
+        self.reset_error_stats();
+    }
+}
+
+/// Its timeout doesn't depend on the baud rate.
+impl<U, OS> UartReconfigure for UartIdleRx<U, OS>
+where
+    U: UartPeriph,
+    OS: OsInterface,
+{
+    fn suspend(&mut self, _timeout: MicrosDurationU32) -> Result<(), Error> {
+        self.uart.set_interrupt(Event::RxNotEmpty, false);
+        Ok(())
+    }
+
+    fn resume(&mut self, _baudrate: u32) {
+        let n = self.r.slots();
+        if let Ok(chunk) = self.r.read_chunk(n) {
+            chunk.commit_all();
+        }
+        // Keep it in step with the count of the interrupt handler
+        self.read_count = self.read_count.wrapping_add(n);
+        self.err.clear();
+        self.uart.set_interrupt(Event::RxNotEmpty, true);
+    }
+}
+
+// RX interrupt -----------------
+
+pub struct UartIdleRxHandler<U, OS: OsInterface> {
+    uart: U,
+    w: Producer<u8>,
+    notifier: OS::Notifier,
+    err: Arc<RxErrorState>,
+    recv_count: usize,
+}
+
+impl<U, OS> UartIdleRxHandler<U, OS>
+where
+    U: UartPeriph,
+    OS: OsInterface,
+{
+    /// Call it in the UART interrupt callback.
+    pub fn handler(&mut self) {
+        let status = self.uart.get_status();
+        self.on_event(&status);
+        // The flag is already cleared if the data register is read.
+        if status.idle {
+            self.uart.check_and_clear_interrupt(Event::Idle);
+        }
+    }
+}
+
+impl<U, OS> UartEventHandler for UartIdleRxHandler<U, OS>
+where
+    U: UartPeriph,
+    OS: OsInterface,
+{
+    fn on_event(&mut self, status: &UartStatus) {
+        // The error flags are not masked, ignore them while the receiver is paused.
+        if status.rx_not_empty
+            || (status.rx_error.is_some() && self.uart.is_interrupt_enable(Event::RxNotEmpty))
+        {
+            match self.uart.read() {
+                // The break character is not stored
+                Ok(data) if is_break(status.rx_error, data) => {
+                    self.err.record(Error::Break, self.recv_count);
+                    self.notifier.notify();
+                }
+                rst => {
+                    if let Some(e) = status.rx_error {
+                        self.err.record(e, self.recv_count);
+                        self.notifier.notify();
+                    }
+
+                    if let Ok(data) = rst {
+                        if self.w.push(data as u8).is_ok() {
+                            self.recv_count = self.recv_count.wrapping_add(1);
+                        } else {
+                            // It's counted in the overrun errors
+                            self.err.record(Error::Overrun, self.recv_count);
+                            self.notifier.notify();
+                        }
+                        if self.w.buffer().capacity() - self.w.slots() < 4 {
+                            self.notifier.notify();
+                        }
+                    }
+                }
+            }
+        }
+
+        if status.idle {
+            self.notifier.notify();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::{fugit::ExtU32, os_trait::FakeOs, uart::mock::MockUart};
+
+    #[test]
+    fn receive() {
+        let u = MockUart::default();
+        let (mut rx, mut it) = UartIdleRx::<_, FakeOs>::new([u.clone(), u.clone()], 4, 0.micros());
+        rx.mark_error_position(true);
+        let mut buf = [0; 8];
+
+        // The data before an error are read first
+        u.receive(1, None);
+        it.handler();
+        u.receive(2, Some(Error::Parity));
+        it.handler();
+        assert_eq!(rx.read(&mut buf), Ok(1));
+        assert_eq!(buf[0], 1);
+        assert_eq!(rx.read(&mut buf), Err(Error::Parity));
+        assert_eq!(rx.read(&mut buf), Ok(1));
+        assert_eq!(buf[0], 2);
+        assert_eq!(rx.read(&mut buf), Err(Error::Timeout));
+
+        // The idle flag is cleared
+        u.0.borrow_mut().idle = true;
+        it.handler();
+        assert!(!u.0.borrow().idle);
+
+        // Overflow
+        for i in 0..6 {
+            u.receive(i, None);
+            it.handler();
+        }
+        assert_eq!(rx.read(&mut buf), Ok(4));
+        assert_eq!(buf[..4], [0, 1, 2, 3]);
+        assert_eq!(rx.read(&mut buf), Err(Error::Overrun));
+        assert_eq!(rx.error_stats().overrun, 2);
+
+        u.receive(6, None);
+        it.handler();
+        assert_eq!(rx.read(&mut buf), Ok(1));
+        assert_eq!(buf[0], 6);
+    }
+}
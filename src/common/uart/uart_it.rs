@@ -2,6 +2,7 @@
 
 use super::*;
 use crate::common::{
+    embedded_hal_nb as e_nb,
     embedded_io::{BufRead, ErrorType, Read, ReadReady, Write, WriteReady},
     os_trait::Duration,
     ringbuf::*,
@@ -9,28 +10,30 @@ use crate::common::{
 
 // TX -------------------------------------------------------------------------
 
-pub struct UartInterruptTx<U, OS: OsInterface> {
+/// `W` is `u16` for 9-bit data.
+pub struct UartInterruptTx<U, OS: OsInterface, W = u8> {
     uart: U,
     timeout: MicrosDurationU32,
     flush_timeout: MicrosDurationU32,
-    w: Producer<u8>,
+    w: Producer<W>,
     waiter: OS::NotifyWaiter,
 }
 
-impl<U, OS> UartInterruptTx<U, OS>
+impl<U, OS, W> UartInterruptTx<U, OS, W>
 where
     U: UartPeriph,
     OS: OsInterface,
+    W: UartWord,
 {
     pub fn new(
         uart: [U; 2],
         buf_size: usize,
         baudrate: u32,
         timeout: MicrosDurationU32,
-    ) -> (Self, UartInterruptTxHandler<U, OS>) {
+    ) -> (Self, UartInterruptTxHandler<U, OS, W>) {
         let (notifier, waiter) = OS::notify();
         let [uart, u2] = uart;
-        let (w, r) = RingBuffer::<u8>::new(buf_size);
+        let (w, r) = RingBuffer::<W>::new(buf_size);
         (
             Self {
                 uart,
@@ -42,18 +45,34 @@ where
             UartInterruptTxHandler::new(u2, r, notifier),
         )
     }
-}
 
-impl<U: UartPeriph, OS: OsInterface> ErrorType for UartInterruptTx<U, OS> {
-    type Error = Error;
+    /// Update the timeouts after the baud rate is changed.
+    pub fn set_baudrate(&mut self, baudrate: u32) {
+        self.flush_timeout = calculate_timeout(baudrate, self.w.buffer().capacity() + 10);
+    }
+
+    /// The time to wait for free space in a write, zero makes it non-blocking.
+    /// Returns [`Error::Timeout`] if nothing is written.
+    pub fn set_timeout(&mut self, timeout: MicrosDurationU32) {
+        self.timeout = timeout;
+    }
+
+    /// Send a break character after the current character.
+    /// Flush it first to send the break after all the buffered data.
+    #[inline]
+    pub fn send_break(&mut self) {
+        self.uart.send_break();
+    }
 }
 
-impl<U, OS> Write for UartInterruptTx<U, OS>
+impl<U, OS, W> UartInterruptTx<U, OS, W>
 where
     U: UartPeriph,
     OS: OsInterface,
+    W: UartWord,
 {
-    fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+    /// Same as [`Write::write`], but for any word type.
+    pub fn write_words(&mut self, buf: &[W]) -> Result<usize, Error> {
         if buf.is_empty() {
             return Err(Error::Other);
         }
@@ -68,12 +87,20 @@ where
                 }
                 None
             })
-            .ok_or(Error::Busy)
+            .ok_or(Error::Timeout)
     }
 
-    fn flush(&mut self) -> Result<(), Self::Error> {
+    /// Same as [`Write::flush`], but for any word type.
+    #[inline]
+    pub fn flush_words(&mut self) -> Result<(), Error> {
+        self.flush_with_timeout(self.flush_timeout)
+    }
+
+    /// Same as [`UartInterruptTx::flush_words`], but it waits for `timeout` instead of
+    /// the time needed to send the whole buffer.
+    pub fn flush_with_timeout(&mut self, timeout: MicrosDurationU32) -> Result<(), Error> {
         self.waiter
-            .wait_with(&Duration::<OS>::micros(self.flush_timeout.ticks()), || {
+            .wait_with(&Duration::<OS>::micros(timeout.ticks()), || {
                 if self.uart.is_tx_complete() && self.w.is_empty() {
                     return Some(());
                 } else if !self.uart.is_interrupt_enable(Event::TxEmpty) {
@@ -81,14 +108,84 @@ where
                 }
                 None
             })
-            .ok_or(Error::Other)
+            .ok_or(Error::Timeout)
+    }
+}
+
+impl<U, OS, W> UartReconfigure for UartInterruptTx<U, OS, W>
+where
+    U: UartPeriph,
+    OS: OsInterface,
+    W: UartWord,
+{
+    #[inline]
+    fn suspend(&mut self, timeout: MicrosDurationU32) -> Result<(), Error> {
+        self.flush_with_timeout(timeout)
+    }
+
+    #[inline]
+    fn resume(&mut self, baudrate: u32) {
+        self.set_baudrate(baudrate);
+    }
+}
+
+impl<U: UartPeriph, OS: OsInterface, W: UartWord> ErrorType for UartInterruptTx<U, OS, W> {
+    type Error = Error;
+}
+
+impl<U, OS> Write for UartInterruptTx<U, OS>
+where
+    U: UartPeriph,
+    OS: OsInterface,
+{
+    #[inline]
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        self.write_words(buf)
+    }
+
+    #[inline]
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        self.flush_words()
     }
 }
 
-impl<U, OS> WriteReady for UartInterruptTx<U, OS>
+impl<U, OS, W> e_nb::serial::ErrorType for UartInterruptTx<U, OS, W>
 where
     U: UartPeriph,
     OS: OsInterface,
+    W: UartWord,
+{
+    type Error = Error;
+}
+
+impl<U, OS> e_nb::serial::Write<u16> for UartInterruptTx<U, OS, u16>
+where
+    U: UartPeriph,
+    OS: OsInterface,
+{
+    fn write(&mut self, word: u16) -> nb::Result<(), Self::Error> {
+        if self.w.push(word).is_ok() {
+            self.uart.set_interrupt(Event::TxEmpty, true);
+            Ok(())
+        } else {
+            Err(nb::Error::WouldBlock)
+        }
+    }
+
+    fn flush(&mut self) -> nb::Result<(), Self::Error> {
+        if self.uart.is_tx_complete() && self.w.is_empty() {
+            Ok(())
+        } else {
+            Err(nb::Error::WouldBlock)
+        }
+    }
+}
+
+impl<U, OS, W> WriteReady for UartInterruptTx<U, OS, W>
+where
+    U: UartPeriph,
+    OS: OsInterface,
+    W: UartWord,
 {
     fn write_ready(&mut self) -> Result<bool, Self::Error> {
         Ok(!self.w.is_full())
@@ -97,31 +194,39 @@ where
 
 // TX interrupt -----------------
 
-pub struct UartInterruptTxHandler<U, OS: OsInterface> {
+pub struct UartInterruptTxHandler<U, OS: OsInterface, W = u8> {
     uart: U,
-    r: Consumer<u8>,
+    r: Consumer<W>,
     notifier: OS::Notifier,
 }
 
-impl<U, OS> UartInterruptTxHandler<U, OS>
+impl<U, OS, W> UartInterruptTxHandler<U, OS, W>
 where
     U: UartPeriph,
     OS: OsInterface,
+    W: UartWord,
 {
-    pub fn new(uart: U, r: Consumer<u8>, notifier: OS::Notifier) -> Self {
+    pub fn new(uart: U, r: Consumer<W>, notifier: OS::Notifier) -> Self {
         Self { uart, r, notifier }
     }
 }
 
-impl<U, OS> UartInterruptTxHandler<U, OS>
+impl<U, OS, W> UartInterruptTxHandler<U, OS, W>
 where
     U: UartPeriph,
     OS: OsInterface,
+    W: UartWord,
 {
+    /// There is no data waiting to be transferred.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.r.is_empty()
+    }
+
     pub fn handler(&mut self) {
         if self.uart.is_tx_complete() {
             if let Ok(data) = self.r.pop() {
-                self.uart.write_unchecked(data as u16);
+                self.uart.write_unchecked(data.into_u16());
                 if self.r.buffer().capacity() - self.r.slots() < 4 {
                     self.notifier.notify();
                 }
@@ -132,56 +237,114 @@ where
     }
 }
 
+impl<U, OS, W> UartEventHandler for UartInterruptTxHandler<U, OS, W>
+where
+    U: UartPeriph,
+    OS: OsInterface,
+    W: UartWord,
+{
+    #[inline]
+    fn on_event(&mut self, status: &UartStatus) {
+        if status.tx_empty {
+            self.handler();
+        }
+    }
+}
+
 // RX -------------------------------------------------------------------------
 
-pub struct UartInterruptRx<U, OS: OsInterface> {
+/// `W` is `u16` for 9-bit data.
+pub struct UartInterruptRx<U, OS: OsInterface, W = u8> {
     uart: U,
     timeout: MicrosDurationU32,
-    r: Consumer<u8>,
+    r: Consumer<W>,
     waiter: OS::NotifyWaiter,
+    err: Arc<RxErrorState>,
+    read_count: usize,
+    mark_error_position: bool,
 }
 
-impl<U, OS> UartInterruptRx<U, OS>
+impl<U, OS, W> UartInterruptRx<U, OS, W>
 where
     U: UartPeriph,
     OS: OsInterface,
+    W: UartWord,
 {
     pub fn new(
         uart: [U; 2],
         buf_size: usize,
         timeout: MicrosDurationU32,
-    ) -> (Self, UartInterruptRxHandler<U, OS>) {
+    ) -> (Self, UartInterruptRxHandler<U, OS, W>) {
         let (notifier, waiter) = OS::notify();
         let [uart, u2] = uart;
-        let (w, r) = RingBuffer::<u8>::new(buf_size);
+        let (w, r) = RingBuffer::<W>::new(buf_size);
+        let err = Arc::new(RxErrorState::new());
         (
             Self {
                 uart,
                 timeout,
                 r,
                 waiter,
+                err: Arc::clone(&err),
+                read_count: 0,
+                mark_error_position: false,
             },
-            UartInterruptRxHandler::new(u2, w, notifier),
+            UartInterruptRxHandler::new(u2, w, notifier, err),
         )
     }
-}
 
-impl<U: UartPeriph, OS: OsInterface> ErrorType for UartInterruptRx<U, OS> {
-    type Error = Error;
+    /// If it's enabled, the data received before an error are read first, then the error is
+    /// returned, and the word with the error follows.
+    /// Otherwise, the error is returned from the next read.
+    ///
+    /// A received break is returned as [`Error::Break`], the break character itself is dropped.
+    pub fn mark_error_position(&mut self, enable: bool) {
+        self.mark_error_position = enable;
+    }
+
+    pub fn error_stats(&self) -> UartErrorStats {
+        self.err.stats()
+    }
+
+    pub fn reset_error_stats(&mut self) {
+        self.err.reset_stats();
+    }
+
+    /// The time to wait for the first word in a read, zero makes it non-blocking.
+    pub fn set_timeout(&mut self, timeout: MicrosDurationU32) {
+        self.timeout = timeout;
+    }
 }
 
-impl<U, OS> Read for UartInterruptRx<U, OS>
+impl<U, OS, W> UartInterruptRx<U, OS, W>
 where
     U: UartPeriph,
     OS: OsInterface,
+    W: UartWord,
 {
-    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+    /// Same as [`Read::read`], but for any word type.
+    #[inline]
+    pub fn read_words(&mut self, buf: &mut [W]) -> Result<usize, Error> {
+        self.read_with_timeout(buf, self.timeout)
+    }
+
+    /// Same as [`UartInterruptRx::read_words`], but it waits for `timeout` instead of the default.
+    /// Returns [`Error::Timeout`] if no word is received.
+    pub fn read_with_timeout(
+        &mut self,
+        buf: &mut [W],
+        timeout: MicrosDurationU32,
+    ) -> Result<usize, Error> {
         if buf.is_empty() {
             return Err(Error::Other);
         }
 
-        self.waiter
-            .wait_with(&Duration::<OS>::micros(self.timeout.ticks()), || {
+        let max = self.err.check(self.read_count, self.mark_error_position)?;
+        let len = max.min(buf.len());
+        let buf = &mut buf[..len];
+        let n = self
+            .waiter
+            .wait_with(&Duration::<OS>::micros(timeout.ticks()), || {
                 if let n @ 1.. = self.r.pop_slice(buf) {
                     return Some(n);
                 } else if !self.uart.is_interrupt_enable(Event::RxNotEmpty) {
@@ -189,7 +352,70 @@ where
                 }
                 None
             })
-            .ok_or(Error::Other)
+            .ok_or(Error::Timeout)?;
+        self.read_count = self.read_count.wrapping_add(n);
+        Ok(n)
+    }
+}
+
+/// Its timeout doesn't depend on the baud rate.
+impl<U, OS, W> UartReconfigure for UartInterruptRx<U, OS, W>
+where
+    U: UartPeriph,
+    OS: OsInterface,
+    W: UartWord,
+{
+    fn suspend(&mut self, _timeout: MicrosDurationU32) -> Result<(), Error> {
+        self.uart.set_interrupt(Event::RxNotEmpty, false);
+        Ok(())
+    }
+
+    fn resume(&mut self, _baudrate: u32) {
+        let n = self.r.slots();
+        if let Ok(chunk) = self.r.read_chunk(n) {
+            chunk.commit_all();
+        }
+        // Keep it in step with the count of the interrupt handler
+        self.read_count = self.read_count.wrapping_add(n);
+        self.err.clear();
+        self.uart.set_interrupt(Event::RxNotEmpty, true);
+    }
+}
+
+impl<U: UartPeriph, OS: OsInterface, W: UartWord> ErrorType for UartInterruptRx<U, OS, W> {
+    type Error = Error;
+}
+
+impl<U, OS> Read for UartInterruptRx<U, OS>
+where
+    U: UartPeriph,
+    OS: OsInterface,
+{
+    #[inline]
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        self.read_words(buf)
+    }
+}
+
+impl<U, OS, W> e_nb::serial::ErrorType for UartInterruptRx<U, OS, W>
+where
+    U: UartPeriph,
+    OS: OsInterface,
+    W: UartWord,
+{
+    type Error = Error;
+}
+
+impl<U, OS> e_nb::serial::Read<u16> for UartInterruptRx<U, OS, u16>
+where
+    U: UartPeriph,
+    OS: OsInterface,
+{
+    fn read(&mut self) -> nb::Result<u16, Self::Error> {
+        self.err.check(self.read_count, self.mark_error_position)?;
+        let data = self.r.pop().map_err(|_| nb::Error::WouldBlock)?;
+        self.read_count = self.read_count.wrapping_add(1);
+        Ok(data)
     }
 }
 
@@ -199,31 +425,35 @@ where
     OS: OsInterface,
 {
     fn fill_buf(&mut self) -> Result<&[u8], Self::Error> {
+        let max = self.err.check(self.read_count, self.mark_error_position)?;
         self.waiter
             .wait_with(&Duration::<OS>::micros(self.timeout.ticks()), || {
                 if let Some(chunk) = self.r.get_read_chunk() {
                     let buf = chunk.get_slice();
                     let p = buf.as_ptr();
-                    return unsafe { Some(core::slice::from_raw_parts(p, buf.len())) };
+                    let len = buf.len().min(max);
+                    return unsafe { Some(core::slice::from_raw_parts(p, len)) };
                 } else if !self.uart.is_interrupt_enable(Event::RxNotEmpty) {
                     self.uart.set_interrupt(Event::RxNotEmpty, true);
                 }
                 None
             })
-            .ok_or(Error::Other)
+            .ok_or(Error::Timeout)
     }
 
     fn consume(&mut self, amt: usize) {
         if let Ok(chunk) = self.r.read_chunk(amt) {
             chunk.commit_all();
+            self.read_count = self.read_count.wrapping_add(amt);
         }
     }
 }
 
-impl<U, OS> ReadReady for UartInterruptRx<U, OS>
+impl<U, OS, W> ReadReady for UartInterruptRx<U, OS, W>
 where
     U: UartPeriph,
     OS: OsInterface,
+    W: UartWord,
 {
     fn read_ready(&mut self) -> Result<bool, Self::Error> {
         Ok(self.r.peek().is_ok())
@@ -232,50 +462,81 @@ where
 
 // RX interrupt -----------------
 
-pub struct UartInterruptRxHandler<U, OS: OsInterface> {
+pub struct UartInterruptRxHandler<U, OS: OsInterface, W = u8> {
     uart: U,
-    w: Producer<u8>,
+    w: Producer<W>,
     notifier: OS::Notifier,
-    // count: [u32; 10],
+    err: Arc<RxErrorState>,
+    recv_count: usize,
 }
 
-impl<U, OS> UartInterruptRxHandler<U, OS>
+impl<U, OS, W> UartInterruptRxHandler<U, OS, W>
 where
     U: UartPeriph,
     OS: OsInterface,
+    W: UartWord,
 {
-    pub fn new(mut uart: U, w: Producer<u8>, notifier: OS::Notifier) -> Self {
+    pub(crate) fn new(
+        mut uart: U,
+        w: Producer<W>,
+        notifier: OS::Notifier,
+        err: Arc<RxErrorState>,
+    ) -> Self {
         uart.set_interrupt(Event::RxNotEmpty, true);
         Self {
             uart,
             w,
             notifier,
-            // count: [0; 10],
+            err,
+            recv_count: 0,
         }
     }
 
     pub fn handler(&mut self) {
-        if let Ok(data) = self.uart.read() {
-            self.w.push(data as u8).ok();
-            if self.w.buffer().capacity() - self.w.slots() < 4 {
+        // The error flags must be checked before reading the data register
+        let err = self.uart.get_rx_error();
+        self.receive(err);
+    }
+
+    fn receive(&mut self, err: Option<Error>) {
+        match self.uart.read() {
+            // The break character is not stored
+            Ok(data) if is_break(err, data) => {
+                self.err.record(Error::Break, self.recv_count);
                 self.notifier.notify();
             }
+            rst => {
+                if let Some(e) = err {
+                    self.err.record(e, self.recv_count);
+                    self.notifier.notify();
+                }
+
+                if let Ok(data) = rst {
+                    if self.w.push(W::from_u16(data)).is_ok() {
+                        self.recv_count = self.recv_count.wrapping_add(1);
+                    }
+                    if self.w.buffer().capacity() - self.w.slots() < 4 {
+                        self.notifier.notify();
+                    }
+                }
+            }
         }
+    }
+}
 
-        // match self.uart.read() {
-        //     Ok(data) => match self.w.push(data as u8) {
-        //         Ok(()) => self.count[0] = self.count[0].saturating_add(1),
-        //         Err(_) => self.count[1] = self.count[1].saturating_add(1),
-        //     },
-        //     Err(nb::Error::WouldBlock) => self.count[2] = self.count[2].saturating_add(1),
-        //     Err(nb::Error::Other(e)) => match e {
-        //         Error::Overrun => self.count[3] = self.count[3].saturating_add(1),
-        //         Error::Other => self.count[4] = self.count[4].saturating_add(1),
-        //         Error::Noise => self.count[5] = self.count[5].saturating_add(1),
-        //         Error::FrameFormat => self.count[6] = self.count[6].saturating_add(1),
-        //         Error::Parity => self.count[7] = self.count[7].saturating_add(1),
-        //         Error::Busy => self.count[8] = self.count[8].saturating_add(1),
-        //     },
-        // }
+impl<U, OS, W> UartEventHandler for UartInterruptRxHandler<U, OS, W>
+where
+    U: UartPeriph,
+    OS: OsInterface,
+    W: UartWord,
+{
+    #[inline]
+    fn on_event(&mut self, status: &UartStatus) {
+        // The error flags are not masked, ignore them while the receiver is paused.
+        if status.rx_not_empty
+            || (status.rx_error.is_some() && self.uart.is_interrupt_enable(Event::RxNotEmpty))
+        {
+            self.receive(status.rx_error);
+        }
     }
 }
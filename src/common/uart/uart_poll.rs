@@ -22,6 +22,50 @@ impl<U: UartPeriph, OS: OsInterface> UartPollTx<U, OS> {
             _os: PhantomData,
         }
     }
+
+    /// Update the timeouts after the baud rate is changed.
+    pub fn set_baudrate(&mut self, baudrate: u32) {
+        self.flush_timeout = calculate_timeout(baudrate, 4);
+    }
+
+    /// The time to wait for the transmitter in a write, zero makes it non-blocking.
+    /// Returns [`Error::Timeout`] if nothing is written.
+    pub fn set_timeout(&mut self, timeout: MicrosDurationU32) {
+        self.timeout = timeout;
+    }
+
+    /// Same as [`e_io::Write::flush`], but it waits for `timeout` instead of the time needed
+    /// to send the data in the transmitter.
+    pub fn flush_with_timeout(&mut self, timeout: MicrosDurationU32) -> Result<(), Error> {
+        let mut t = Timeout::<OS>::micros(timeout.to_micros());
+        loop {
+            if self.uart.is_tx_complete() {
+                return Ok(());
+            }
+
+            if t.timeout() {
+                return Err(Error::Timeout);
+            }
+        }
+    }
+
+    /// Send a break character after the current character.
+    #[inline]
+    pub fn send_break(&mut self) {
+        self.uart.send_break();
+    }
+}
+
+impl<U: UartPeriph, OS: OsInterface> UartReconfigure for UartPollTx<U, OS> {
+    #[inline]
+    fn suspend(&mut self, timeout: MicrosDurationU32) -> Result<(), Error> {
+        self.flush_with_timeout(timeout)
+    }
+
+    #[inline]
+    fn resume(&mut self, baudrate: u32) {
+        self.set_baudrate(baudrate);
+    }
 }
 
 impl<U: UartPeriph, OS: OsInterface> e_nb::serial::ErrorType for UartPollTx<U, OS> {
@@ -50,8 +94,9 @@ impl<U: UartPeriph, OS: OsInterface> e_nb::serial::Write<u16> for UartPollTx<U,
 
 // IO Write ----
 
-impl<U: UartPeriph, OS: OsInterface> e_io::Write for UartPollTx<U, OS> {
-    fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+impl<U: UartPeriph, OS: OsInterface> UartPollTx<U, OS> {
+    /// Same as [`e_io::Write::write`], but `u16` can be used for 9-bit data.
+    pub fn write_words<W: UartWord>(&mut self, buf: &[W]) -> Result<usize, Error> {
         if buf.is_empty() {
             return Err(Error::Other);
         }
@@ -59,7 +104,7 @@ impl<U: UartPeriph, OS: OsInterface> e_io::Write for UartPollTx<U, OS> {
         // try first data
         let mut t = Timeout::<OS>::micros(self.timeout.to_micros());
         let rst = loop {
-            let rst = self.uart.write(buf[0] as u16);
+            let rst = self.uart.write(buf[0].into_u16());
             if let Err(nb::Error::WouldBlock) = rst {
                 if t.timeout() {
                     break rst;
@@ -71,31 +116,29 @@ impl<U: UartPeriph, OS: OsInterface> e_io::Write for UartPollTx<U, OS> {
 
         match rst {
             Ok(()) => (),
-            Err(nb::Error::WouldBlock) => return Err(Error::Busy),
+            Err(nb::Error::WouldBlock) => return Err(Error::Timeout),
             Err(nb::Error::Other(_)) => return Err(Error::Other),
         }
 
         // write rest data
         for (i, &data) in buf[1..buf.len()].iter().enumerate() {
-            if self.uart.write(data as u16).is_err() {
+            if self.uart.write(data.into_u16()).is_err() {
                 return Ok(i + 1);
             }
         }
         Ok(buf.len())
     }
+}
 
-    fn flush(&mut self) -> Result<(), Self::Error> {
-        let mut t = Timeout::<OS>::micros(self.flush_timeout.to_micros());
-        loop {
-            if self.uart.is_tx_complete() {
-                return Ok(());
-            }
+impl<U: UartPeriph, OS: OsInterface> e_io::Write for UartPollTx<U, OS> {
+    #[inline]
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        self.write_words(buf)
+    }
 
-            if t.timeout() {
-                break;
-            }
-        }
-        Err(Error::Other)
+    #[inline]
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        self.flush_with_timeout(self.flush_timeout)
     }
 }
 
@@ -117,6 +160,29 @@ impl<U: UartPeriph, OS: OsInterface> UartPollRx<U, OS> {
             _os: PhantomData,
         }
     }
+
+    /// Update the timeouts after the baud rate is changed.
+    pub fn set_baudrate(&mut self, baudrate: u32) {
+        self.continue_timeout = calculate_timeout(baudrate, 4);
+    }
+
+    /// The time to wait for the first word in a read, zero makes it non-blocking.
+    pub fn set_timeout(&mut self, timeout: MicrosDurationU32) {
+        self.timeout = timeout;
+    }
+}
+
+/// The data register is cleared by `UartControl::reconfigure`.
+impl<U: UartPeriph, OS: OsInterface> UartReconfigure for UartPollRx<U, OS> {
+    #[inline]
+    fn suspend(&mut self, _timeout: MicrosDurationU32) -> Result<(), Error> {
+        Ok(())
+    }
+
+    #[inline]
+    fn resume(&mut self, baudrate: u32) {
+        self.set_baudrate(baudrate);
+    }
 }
 
 impl<U: UartPeriph, OS: OsInterface> e_nb::serial::ErrorType for UartPollRx<U, OS> {
@@ -137,14 +203,26 @@ impl<U: UartPeriph, OS: OsInterface> e_nb::serial::Read<u16> for UartPollRx<U, O
 
 // IO Read ----
 
-impl<U: UartPeriph, OS: OsInterface> e_io::Read for UartPollRx<U, OS> {
-    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+impl<U: UartPeriph, OS: OsInterface> UartPollRx<U, OS> {
+    /// Same as [`e_io::Read::read`], but `u16` can be used for 9-bit data.
+    #[inline]
+    pub fn read_words<W: UartWord>(&mut self, buf: &mut [W]) -> Result<usize, Error> {
+        self.read_with_timeout(buf, self.timeout)
+    }
+
+    /// Same as [`UartPollRx::read_words`], but it waits for `timeout` instead of the default.
+    /// Returns [`Error::Timeout`] if no word is received.
+    pub fn read_with_timeout<W: UartWord>(
+        &mut self,
+        buf: &mut [W],
+        timeout: MicrosDurationU32,
+    ) -> Result<usize, Error> {
         if buf.is_empty() {
             return Err(Error::Other);
         }
 
         // try first data
-        let mut t = Timeout::<OS>::micros(self.timeout.to_micros());
+        let mut t = Timeout::<OS>::micros(timeout.to_micros());
         let rst = loop {
             let rst = self.uart.read();
             if let Err(nb::Error::WouldBlock) = rst {
@@ -157,8 +235,9 @@ impl<U: UartPeriph, OS: OsInterface> e_io::Read for UartPollRx<U, OS> {
         };
 
         match rst {
-            Ok(data) => buf[0] = data as u8,
-            _ => return Err(Error::Other),
+            Ok(data) => buf[0] = W::from_u16(data),
+            Err(nb::Error::WouldBlock) => return Err(Error::Timeout),
+            Err(nb::Error::Other(e)) => return Err(e),
         }
 
         let mut t = Timeout::<OS>::micros(self.continue_timeout.to_micros());
@@ -166,7 +245,7 @@ impl<U: UartPeriph, OS: OsInterface> e_io::Read for UartPollRx<U, OS> {
         while n < buf.len() {
             match self.uart.read() {
                 Ok(data) => {
-                    buf[n] = data as u8;
+                    buf[n] = W::from_u16(data);
                     n += 1;
                     t.restart();
                 }
@@ -181,3 +260,10 @@ impl<U: UartPeriph, OS: OsInterface> e_io::Read for UartPollRx<U, OS> {
         Ok(buf.len())
     }
 }
+
+impl<U: UartPeriph, OS: OsInterface> e_io::Read for UartPollRx<U, OS> {
+    #[inline]
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        self.read_words(buf)
+    }
+}
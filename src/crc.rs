@@ -0,0 +1,141 @@
+//! # Cyclic Redundancy Check calculation unit
+//!
+//! The hardware computes CRC-32 (polynomial `0x04C11DB7`) over 32-bit words,
+//! most significant bit first. The initial value is `0xFFFF_FFFF` and there is
+//! no final XOR.
+
+pub use crate::common::crc::*;
+
+use crate::{
+    dma::{Ch, DmaChannel, RegisterBlock},
+    pac::CRC,
+    rcc::Rcc,
+};
+
+pub trait CrcInit {
+    fn init(self, rcc: &mut Rcc) -> Crc;
+}
+
+impl CrcInit for CRC {
+    fn init(self, rcc: &mut Rcc) -> Crc {
+        rcc.enable(&self);
+        let mut crc = Crc { crc: self };
+        crc.reset();
+        crc
+    }
+}
+
+pub struct Crc {
+    crc: CRC,
+}
+
+impl Crc {
+    /// Reset the calculation, the data register is set to `0xFFFF_FFFF`.
+    #[inline]
+    pub fn reset(&mut self) {
+        self.crc.reset();
+    }
+
+    /// Feed words and return the CRC of all data since the last reset.
+    pub fn update(&mut self, data: &[u32]) -> u32 {
+        for &word in data {
+            self.write(word);
+        }
+        self.get_crc()
+    }
+
+    /// Feed bytes and return the CRC of all data since the last reset.
+    ///
+    /// Every 4 bytes are packed into a word in big-endian order, so `data[0]`
+    /// goes first. The last partial word is padded with zeros.
+    pub fn update_bytes(&mut self, data: &[u8]) -> u32 {
+        let mut chunks = data.chunks_exact(4);
+        for c in &mut chunks {
+            self.write(u32::from_be_bytes([c[0], c[1], c[2], c[3]]));
+        }
+
+        let rest = chunks.remainder();
+        if !rest.is_empty() {
+            let mut word = [0; 4];
+            word[..rest.len()].copy_from_slice(rest);
+            self.write(u32::from_be_bytes(word));
+        }
+        self.get_crc()
+    }
+
+    /// Feed words through a DMA memory-to-memory transfer and return the CRC
+    /// of all data since the last reset.
+    ///
+    /// It's blocking until the whole buffer is transferred.
+    pub fn update_dma<DMA, const C: u8>(&mut self, ch: &mut Ch<DMA, C>, data: &[u32]) -> u32
+    where
+        DMA: RegisterBlock,
+    {
+        let dr = self.crc.dr().as_ptr() as usize;
+        // The maximum transfer length is 65535
+        for chunk in data.chunks(u16::MAX as usize) {
+            ch.stop();
+            ch.set_peripheral_address::<u32>(dr, true, false, false);
+            ch.set_memory_buf_for_peripheral(chunk);
+            ch.set_mem2mem(true);
+            ch.start();
+            while ch.in_progress() {}
+        }
+        ch.stop();
+        ch.set_mem2mem(false);
+        self.get_crc()
+    }
+
+    /// Write a word to the data register
+    #[inline(always)]
+    pub fn write(&mut self, word: u32) {
+        self.crc.write(word);
+    }
+
+    /// The current result
+    #[inline(always)]
+    pub fn get_crc(&self) -> u32 {
+        self.crc.get_crc()
+    }
+
+    /// A general-purpose byte register, it's not affected by reset.
+    #[inline]
+    pub fn get_idr(&self) -> u8 {
+        self.crc.idr().read().idr().bits()
+    }
+
+    #[inline]
+    pub fn set_idr(&mut self, value: u8) {
+        self.crc.idr().write(|w| w.idr().set(value));
+    }
+
+    /// Switch to the mode that matches the standard CRC-32 (zlib, Ethernet).
+    /// See [`CompatCrc32`].
+    pub fn into_compatible(self) -> CompatCrc32<CRC> {
+        CompatCrc32::new(self.crc)
+    }
+
+    /// Release the CRC peripheral
+    pub fn release(self) -> CRC {
+        self.crc
+    }
+}
+
+// Implement Peripheral -------------------------------------------------------
+
+impl CrcPeriph for CRC {
+    #[inline]
+    fn reset(&mut self) {
+        self.cr().write(|w| w.reset().reset());
+    }
+
+    #[inline(always)]
+    fn write(&mut self, word: u32) {
+        self.dr().write(|w| w.dr().set(word));
+    }
+
+    #[inline(always)]
+    fn get_crc(&self) -> u32 {
+        self.dr().read().bits()
+    }
+}
@@ -90,6 +90,12 @@ where
         self.ch().cr().modify(|_, w| w.pl().variant(priority));
     }
 
+    /// Transfer without waiting for a request from peripheral.
+    #[inline]
+    pub(crate) fn set_mem2mem(&mut self, enable: bool) {
+        self.ch().cr().modify(|_, w| w.mem2mem().bit(enable));
+    }
+
     #[inline(always)]
     fn ch(&self) -> &pac::dma1::CH {
         self.dma.ch(C as usize)
@@ -176,8 +182,18 @@ where
     }
 
     #[inline]
-    fn set_memory_to_memory<T: Sized>(&mut self, _src_addr: usize, _dst_addr: usize, _len: usize) {
-        l::todo!()
+    fn set_memory_to_memory<T: Sized + Copy>(
+        &mut self,
+        src_addr: usize,
+        src_increase: bool,
+        dst_addr: usize,
+        len: usize,
+    ) {
+        // The memory port reads from the source and the peripheral port writes to the destination.
+        self.set_peripheral_address::<T>(dst_addr, true, true, false);
+        self.set_memory_address(src_addr, src_increase);
+        self.set_transfer_length(len);
+        self.set_mem2mem(true);
     }
 
     #[inline]
@@ -221,9 +237,20 @@ where
     }
 }
 
+#[diagnostic::on_unimplemented(
+    message = "`{Self}` is not bound to the TX of `{U}`",
+    note = "see the DMA request table in the reference manual, UART5 has no DMA"
+)]
 pub trait DmaBindTx<U>: DmaChannel {}
+#[diagnostic::on_unimplemented(
+    message = "`{Self}` is not bound to the RX of `{U}`",
+    note = "see the DMA request table in the reference manual, UART5 has no DMA, use `Rx::into_idle_interrupt` instead"
+)]
 pub trait DmaBindRx<U>: DmaChannel {}
 
+impl<U, CH: DmaBindTx<U>> DmaBindTx<U> for DmaChannelLease<CH> {}
+impl<U, CH: DmaBindRx<U>> DmaBindRx<U> for DmaChannelLease<CH> {}
+
 // table
 // Do NOT manually modify the code.
 // It's generated by scripts/generate_dma_table.py from scripts/table/stm32f1_dma_table.csv
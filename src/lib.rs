@@ -9,6 +9,7 @@ cfg_if::cfg_if! {
         pub mod afio;
         pub mod backup_domain;
         pub mod bb;
+        pub mod crc;
         pub mod dma;
         pub mod flash;
         pub mod gpio;
@@ -36,6 +37,7 @@ pub use critical_section;
 pub use fugit;
 pub use os_trait;
 
+pub use embedded_dma;
 pub use embedded_hal;
 pub use embedded_io;
 pub use nb;
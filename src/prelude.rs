@@ -1,4 +1,5 @@
 pub use crate::afio::AfioInit as _;
+pub use crate::crc::CrcInit as _;
 pub use crate::flash::FlashInit as _;
 pub use crate::gpio::GpioExt as _;
 pub use crate::i2c::I2cInit as _;
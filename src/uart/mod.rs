@@ -11,13 +11,21 @@ pub use crate::common::uart::*;
 use crate::{
     Mcu, Steal,
     afio::{RemapMode, uart_remap::*},
-    common::prelude::*,
+    common::{
+        embedded_hal::digital::{InputPin, OutputPin},
+        os_trait::{TickInstant, Timeout},
+        prelude::*,
+    },
     dma::{DmaBindRx, DmaBindTx, DmaRingbufTxLoader},
-    fugit::MicrosDurationU32,
+    fugit::{HertzU32, MicrosDurationU32},
+    gpio::{Alternate, HL, Pin, PushPull},
     rcc::{Enable, GetClock, Reset},
 };
 use core::marker::PhantomData;
 
+/// Typical low-power frequency of IrDA
+const IRDA_LOW_POWER_FREQ: u32 = 1_843_200;
+
 pub trait UartInit<U> {
     fn init<OS: OsInterface>(self, mcu: &mut Mcu) -> Uart<OS, U>;
 }
@@ -26,10 +34,40 @@ pub trait UartPeriphConfig: UartPeriph + GetClock + Enable + Reset + Steal {
     fn config(&mut self, config: Config);
     fn enable_comm(&mut self, tx: bool, rx: bool);
     fn set_stop_bits(&mut self, bits: StopBits);
+    /// Single-wire half-duplex mode, RX pin is not used.
+    fn set_half_duplex(&mut self, enable: bool);
     fn is_tx_empty(&self) -> bool;
     fn is_rx_not_empty(&self) -> bool;
 }
 
+/// Hardware flow control, only USART1, USART2 and USART3 support it.
+pub trait UartPeriphFlowControl: UartPeriphConfig {
+    /// - `rts`: RTS is asserted only when there is space in the receive buffer.
+    /// - `cts`: Data is transmitted only when CTS is asserted.
+    fn set_flow_control(&mut self, rts: bool, cts: bool);
+}
+
+/// Smartcard mode, only USART1, USART2 and USART3 support it.
+pub trait UartPeriphSmartcard: UartPeriphConfig {
+    /// - `prescaler`: The card clock is `PCLK / (2 * prescaler)`, from 1 to 31.
+    /// - `guard_time`: In baud clock units.
+    ///
+    /// NACK is enabled together with smartcard mode.
+    fn set_smartcard(&mut self, enable: bool, prescaler: u8, guard_time: u8);
+}
+
+/// IrDA SIR mode
+pub trait UartPeriphIrda: UartPeriphConfig {
+    /// IRLP is cleared when it's disabled.
+    fn set_irda(&mut self, enable: bool, mode: IrdaMode);
+}
+
+/// Synchronous mode, only USART1, USART2 and USART3 support it.
+pub trait UartPeriphSynchronous: UartPeriphConfig {
+    /// It should be called before the transmitter is enabled.
+    fn set_synchronous(&mut self, enable: bool, config: &SyncConfig);
+}
+
 // wrapper
 pub struct Uart<OS: OsInterface, U> {
     uart: U,
@@ -69,9 +107,320 @@ where
         }
     }
 
+    /// Measure the sync byte on the RX pin and configure the baud rate, before the UART is enabled.
+    /// Pass the returned baud rate in [`Config`] to [`Uart::into_tx_rx`] afterwards.
+    /// The sync byte is consumed.
+    ///
+    /// `T` should have a high resolution, such as [`DwtInstant`](crate::time::DwtInstant).
+    /// Returns `None` on timeout or if the baud rate is not supported.
+    pub fn detect_baudrate<T: TickInstant>(
+        &mut self,
+        rx: &mut impl InputPin,
+        sync: AutoBaudSync,
+        timeout: MicrosDurationU32,
+    ) -> Option<u32> {
+        let baudrate = measure_baudrate::<T>(rx, sync, timeout)?;
+        self.apply_baudrate(baudrate)
+    }
+
+    /// Same as [`Uart::detect_baudrate`], but the sync byte is measured by [`AutoBaudCapture`].
+    /// Returns `None` if it's not measured yet or if the baud rate is not supported.
+    pub fn apply_captured_baudrate<T: TickInstant>(
+        &mut self,
+        capture: &AutoBaudCapture<T>,
+    ) -> Option<u32> {
+        self.apply_baudrate(capture.baudrate()?)
+    }
+
+    /// The divider in BRR is from 16 to 0xFFFF.
+    fn apply_baudrate(&mut self, baudrate: u32) -> Option<u32> {
+        if baudrate == 0 || !(16..=0xFFFF).contains(&(self.uart.get_clock().raw() / baudrate)) {
+            return None;
+        }
+        self.uart.set_baudrate(baudrate);
+        Some(baudrate)
+    }
+
     pub fn get_idle_interrupt_handler(&self) -> UartIdleInterrupt<U> {
         UartIdleInterrupt::new(unsafe { self.uart.steal() })
     }
+
+    /// Attach the interrupt handlers of the transmitter and the receiver to it
+    /// to share the UART interrupt callback.
+    pub fn get_interrupt_dispatcher(&self) -> UartInterruptDispatcher<U> {
+        UartInterruptDispatcher::new(unsafe { self.uart.steal() })
+    }
+
+    /// It can be used to change the configuration after [`Uart::into_tx_rx`].
+    pub fn get_control(&self) -> UartControl<OS, U> {
+        UartControl::new(unsafe { self.uart.steal() })
+    }
+}
+
+impl<OS, U> Uart<OS, U>
+where
+    OS: OsInterface,
+    U: UartPeriphIrda,
+{
+    /// Same as [`Uart::into_tx_rx`], and enable the IrDA SIR encoder and decoder.
+    /// Only 1 stop bit is supported.
+    pub fn into_irda<REMAP: RemapMode<U>>(
+        mut self,
+        pins: (impl UartTxPin<REMAP>, impl UartRxPin<REMAP>),
+        config: Config,
+        mode: IrdaMode,
+        mcu: &mut Mcu,
+    ) -> (Option<Tx<OS, U>>, Option<Rx<OS, U>>) {
+        self.uart.set_irda(true, mode);
+        self.into_tx_rx(pins, config, mcu)
+    }
+}
+
+impl<OS, U> Uart<OS, U>
+where
+    OS: OsInterface,
+    U: UartPeriphConfig + UartPeriphMute,
+{
+    /// Configure the wakeup method of mute mode. It should be called before `into_tx_rx`.
+    /// Use [`WordLength::Bits9`] and the `u16` interface on a 9-bit multiprocessor bus.
+    pub fn set_wakeup(&mut self, method: WakeupMethod, address: u8) {
+        self.uart.set_wakeup(method, address);
+    }
+
+    pub fn get_mute_control(&self) -> UartMuteControl<U> {
+        UartMuteControl::new(unsafe { self.uart.steal() })
+    }
+}
+
+impl<OS, U> Uart<OS, U>
+where
+    OS: OsInterface,
+    U: UartPeriphConfig,
+{
+    /// Single-wire half-duplex mode.
+    /// The TX pin is configured as open-drain, so an external pull-up resistor is required.
+    pub fn into_half_duplex<REMAP: RemapMode<U>, const P: char, const N: u8>(
+        mut self,
+        pin: impl UartTxPin<REMAP, P = Pin<P, N, Alternate<PushPull>>>,
+        config: Config,
+        timeout: MicrosDurationU32,
+        mcu: &mut Mcu,
+    ) -> UartHalfDuplex<U, OS>
+    where
+        Pin<P, N, Alternate<PushPull>>: HL,
+    {
+        let _ = pin.into_alternate().into_alternate_open_drain();
+        REMAP::remap(&mut mcu.afio);
+        let baudrate = config.baudrate;
+        self.uart.config(config);
+        self.uart.set_half_duplex(true);
+        self.uart.enable_comm(true, true);
+        let u2 = unsafe { self.uart.steal() };
+        UartHalfDuplex::new([self.uart, u2], baudrate, timeout)
+    }
+}
+
+impl<OS, U> Uart<OS, U>
+where
+    OS: OsInterface,
+    U: UartPeriphSynchronous,
+{
+    /// Synchronous master mode, the clock is output on the CK pin.
+    /// The receiver is disabled if `NonePin` is given as the RX pin.
+    pub fn into_synchronous<REMAP: RemapMode<U>>(
+        mut self,
+        pins: (
+            impl UartTxPin<REMAP>,
+            impl UartRxPin<REMAP>,
+            impl UartCkPin<REMAP>,
+        ),
+        config: Config,
+        sync_config: SyncConfig,
+        mcu: &mut Mcu,
+    ) -> UartSyncMaster<U, OS> {
+        let rx = pins.1.is_pin();
+        let _ = (pins.0.into_alternate(), pins.2.into_alternate());
+        REMAP::remap(&mut mcu.afio);
+        let baudrate = config.baudrate;
+        self.uart.config(config);
+        self.uart.set_synchronous(true, &sync_config);
+        self.uart.enable_comm(true, rx);
+        UartSyncMaster::new(self.uart, baudrate, rx, sync_config.msb_first)
+    }
+}
+
+impl<OS, U> Uart<OS, U>
+where
+    OS: OsInterface,
+    U: UartPeriphSmartcard,
+{
+    /// Smartcard mode, the card clock is output on the CK pin.
+    /// The TX pin is configured as open-drain, so an external pull-up resistor is required.
+    ///
+    /// The baud rate is `card clock / 372` until it's changed by the user.
+    pub fn into_smartcard<REMAP: RemapMode<U>, const P: char, const N: u8>(
+        mut self,
+        pins: (
+            impl UartTxPin<REMAP, P = Pin<P, N, Alternate<PushPull>>>,
+            impl UartCkPin<REMAP>,
+        ),
+        card_clock: HertzU32,
+        guard_time: u8,
+        mcu: &mut Mcu,
+    ) -> Smartcard<U, OS>
+    where
+        Pin<P, N, Alternate<PushPull>>: HL,
+    {
+        let _ = pins.0.into_alternate().into_alternate_open_drain();
+        let _ = pins.1.into_alternate();
+        REMAP::remap(&mut mcu.afio);
+
+        let pclk = self.uart.get_clock().raw();
+        let psc = pclk.div_ceil(2 * card_clock.raw()).clamp(1, 31);
+        let baudrate = pclk / (2 * psc) / 372;
+        // 8 data bits + even parity, 1.5 stop bits
+        self.uart.config(Config {
+            baudrate,
+            word_length: WordLength::Bits9,
+            parity: Parity::ParityEven,
+            stop_bits: StopBits::STOP1P5,
+        });
+        self.uart.set_smartcard(true, psc as u8, guard_time);
+        self.uart.enable_comm(true, true);
+        Smartcard::new(self.uart, baudrate)
+    }
+}
+
+impl<OS, U> Uart<OS, U>
+where
+    OS: OsInterface,
+    U: UartPeriphConfig + LinPeriph,
+{
+    /// LIN master, 13-bit breaks are sent and `detection` is the length of the detected breaks.
+    pub fn into_lin_master<REMAP: RemapMode<U>>(
+        self,
+        pins: (impl UartTxPin<REMAP>, impl UartRxPin<REMAP>),
+        baudrate: u32,
+        detection: LinBreakDetection,
+        model: LinChecksumModel,
+        mcu: &mut Mcu,
+    ) -> LinMaster<U, OS> {
+        let uart = self.into_lin(pins, baudrate, detection, mcu);
+        LinMaster::new(uart, baudrate, model)
+    }
+
+    /// LIN slave, `frames` are the frames that this slave publishes or subscribes.
+    /// `detection` is the length of the detected breaks.
+    pub fn into_lin_slave<'a, REMAP: RemapMode<U>>(
+        self,
+        pins: (impl UartTxPin<REMAP>, impl UartRxPin<REMAP>),
+        baudrate: u32,
+        detection: LinBreakDetection,
+        frames: &'a [LinFrameInfo],
+        model: LinChecksumModel,
+        mcu: &mut Mcu,
+    ) -> LinSlave<'a, U> {
+        let uart = self.into_lin(pins, baudrate, detection, mcu);
+        LinSlave::new(uart, frames, model)
+    }
+
+    fn into_lin<REMAP: RemapMode<U>>(
+        mut self,
+        pins: (impl UartTxPin<REMAP>, impl UartRxPin<REMAP>),
+        baudrate: u32,
+        detection: LinBreakDetection,
+        mcu: &mut Mcu,
+    ) -> U {
+        let _ = pins.0.into_alternate();
+        REMAP::remap(&mut mcu.afio);
+        // LIN mode requires 8 data bits, no parity and 1 stop bit
+        self.uart.config(Config::default().baudrate(baudrate));
+        self.uart.set_lin_mode(true, detection);
+        self.uart.enable_comm(true, true);
+        self.uart
+    }
+}
+
+impl<OS, U> Uart<OS, U>
+where
+    OS: OsInterface,
+    U: UartPeriphFlowControl,
+{
+    /// Same as [`Uart::into_tx_rx`], and enable hardware flow control.
+    /// RTS or CTS is disabled if `NonePin` is given.
+    pub fn into_tx_rx_with_flow_control<REMAP: RemapMode<U>>(
+        mut self,
+        pins: (
+            impl UartTxPin<REMAP>,
+            impl UartRxPin<REMAP>,
+            impl UartCtsPin<REMAP>,
+            impl UartRtsPin<REMAP>,
+        ),
+        config: Config,
+        mcu: &mut Mcu,
+    ) -> (Option<Tx<OS, U>>, Option<Rx<OS, U>>) {
+        let cts = pins.2.is_pin();
+        let rts = pins.3.is_pin();
+        let _ = pins.3.into_alternate();
+        self.uart.set_flow_control(rts, cts);
+        self.into_tx_rx((pins.0, pins.1), config, mcu)
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+
+/// Change the configuration of a running UART.
+pub struct UartControl<OS: OsInterface, U> {
+    uart: U,
+    _os: PhantomData<OS>,
+}
+
+impl<OS, U> UartControl<OS, U>
+where
+    OS: OsInterface,
+    U: UartPeriphConfig,
+{
+    fn new(uart: U) -> Self {
+        Self {
+            uart,
+            _os: PhantomData,
+        }
+    }
+
+    /// Apply the new configuration to the running transmitter and receiver.
+    /// Pass `&mut ()` for the side that is not used.
+    ///
+    /// 1. The buffered data of `tx` are sent, and `rx` stops receiving.
+    /// 2. The configuration is changed, and the data in the receive register are discarded.
+    /// 3. The unread data of `rx` are discarded, and the timeouts of both sides are updated.
+    ///
+    /// Returns [`Error::Timeout`] if the transmission is not complete within `timeout`,
+    /// then nothing is changed.
+    pub fn reconfigure(
+        &mut self,
+        config: Config,
+        tx: &mut impl UartReconfigure,
+        rx: &mut impl UartReconfigure,
+        timeout: MicrosDurationU32,
+    ) -> Result<(), Error> {
+        let mut t = Timeout::<OS>::micros(timeout.to_micros());
+        tx.suspend(timeout)?;
+        // The interrupt handler disables TXE interrupt when its buffer is empty
+        while self.uart.is_interrupt_enable(Event::TxEmpty) || !self.uart.is_tx_complete() {
+            if t.timeout() {
+                return Err(Error::Timeout);
+            }
+        }
+        rx.suspend(timeout)?;
+
+        let baudrate = config.baudrate;
+        self.uart.config(config);
+        while let Ok(_) | Err(nb::Error::Other(_)) = self.uart.read() {}
+
+        tx.resume(baudrate);
+        rx.resume(baudrate);
+        Ok(())
+    }
 }
 
 // ------------------------------------------------------------------------------------------------
@@ -108,6 +457,44 @@ where
         let u2 = unsafe { self.uart.steal() };
         UartInterruptTx::new([self.uart, u2], buf_size, self.baudrate, timeout)
     }
+
+    /// Same as [`Tx::into_interrupt`], but the words are `u16` for 9-bit data.
+    pub fn into_interrupt_u16(
+        self,
+        buf_size: usize,
+        timeout: MicrosDurationU32,
+    ) -> (
+        UartInterruptTx<U, OS, u16>,
+        UartInterruptTxHandler<U, OS, u16>,
+    ) {
+        let u2 = unsafe { self.uart.steal() };
+        UartInterruptTx::new([self.uart, u2], buf_size, self.baudrate, timeout)
+    }
+
+    /// RS-485 transmitter, `de` is the driver-enable pin of the transceiver.
+    pub fn into_rs485_poll<DE: OutputPin>(
+        self,
+        de: DE,
+        timeout: MicrosDurationU32,
+    ) -> Rs485Tx<UartPollTx<U, OS>, U, DE> {
+        let u2 = unsafe { self.uart.steal() };
+        Rs485Tx::new_poll(self.into_poll(timeout), u2, de)
+    }
+
+    /// RS-485 transmitter, `de` is the driver-enable pin of the transceiver.
+    pub fn into_rs485_interrupt<DE: OutputPin>(
+        self,
+        de: DE,
+        buf_size: usize,
+        timeout: MicrosDurationU32,
+    ) -> (
+        Rs485Tx<UartInterruptTx<U, OS>, U, DE>,
+        Rs485TxHandler<UartInterruptTxHandler<U, OS>, U, DE>,
+    ) {
+        let u2 = unsafe { self.uart.steal() };
+        let (tx, handler) = self.into_interrupt(buf_size, timeout);
+        Rs485Tx::new_interrupt(tx, handler, u2, de)
+    }
 }
 
 impl<OS, U> Tx<OS, U>
@@ -127,6 +514,43 @@ where
     {
         UartDmaBufTx::new(self.uart, dma_ch, buf_size, self.baudrate, timeout)
     }
+
+    /// DMX512 transmitter, the UART should be configured with [`Config::dmx512`].
+    /// `buf_size` should be at least 513 to hold a whole packet.
+    pub fn into_dmx<CH>(
+        self,
+        dma_ch: CH,
+        buf_size: usize,
+        timeout: MicrosDurationU32,
+    ) -> (DmxTx<U, CH, OS>, DmaRingbufTxLoader<u8, CH, OS>)
+    where
+        CH: DmaBindTx<U>,
+    {
+        let u2 = unsafe { self.uart.steal() };
+        let (tx, loader) = self.into_dma_ringbuf(dma_ch, buf_size, timeout);
+        (DmxTx::new(tx, u2), loader)
+    }
+
+    /// RS-485 transmitter, `de` is the driver-enable pin of the transceiver.
+    /// Call [`Rs485TxHandler::interrupt_tx_complete`] in the UART interrupt callback.
+    pub fn into_rs485_dma_ringbuf<CH, DE>(
+        self,
+        de: DE,
+        dma_ch: CH,
+        buf_size: usize,
+        timeout: MicrosDurationU32,
+    ) -> (
+        Rs485Tx<UartDmaBufTx<U, CH, OS>, U, DE>,
+        Rs485TxHandler<DmaRingbufTxLoader<u8, CH, OS>, U, DE>,
+    )
+    where
+        CH: DmaBindTx<U>,
+        DE: OutputPin,
+    {
+        let u2 = unsafe { self.uart.steal() };
+        let (tx, loader) = self.into_dma_ringbuf(dma_ch, buf_size, timeout);
+        Rs485Tx::new_dma(tx, loader, u2, de)
+    }
 }
 
 // ------------------------------------------------------------------------------------------------
@@ -163,6 +587,31 @@ where
         let u2 = unsafe { self.uart.steal() };
         UartInterruptRx::new([self.uart, u2], buf_size, timeout)
     }
+
+    /// Same as [`Rx::into_interrupt`], but the words are `u16` for 9-bit data.
+    pub fn into_interrupt_u16(
+        self,
+        buf_size: usize,
+        timeout: MicrosDurationU32,
+    ) -> (
+        UartInterruptRx<U, OS, u16>,
+        UartInterruptRxHandler<U, OS, u16>,
+    ) {
+        let u2 = unsafe { self.uart.steal() };
+        UartInterruptRx::new([self.uart, u2], buf_size, timeout)
+    }
+
+    /// Same as [`Rx::into_dma_circle`], but the data are received by the RXNE interrupt,
+    /// and the reader is woken at an idle line. It can be used for UART5, which has no DMA.
+    /// Call [`UartIdleRxHandler::handler`] in the UART interrupt callback.
+    pub fn into_idle_interrupt(
+        self,
+        buf_size: usize,
+        timeout: MicrosDurationU32,
+    ) -> (UartIdleRx<U, OS>, UartIdleRxHandler<U, OS>) {
+        let u2 = unsafe { self.uart.steal() };
+        UartIdleRx::new([self.uart, u2], buf_size, timeout)
+    }
 }
 
 impl<OS, U> Rx<OS, U>
@@ -170,6 +619,7 @@ where
     OS: OsInterface,
     U: UartPeriphConfig + UartPeriphWithDma,
 {
+    /// UART5 has no DMA, use [`Rx::into_idle_interrupt`] instead.
     pub fn into_dma_circle<CH>(
         self,
         dma_ch: CH,
@@ -186,4 +636,33 @@ where
     {
         UartDmaRx::new(self.uart, dma_ch, buf_size, timeout)
     }
+
+    /// Receive frames that are separated by idle lines.
+    /// `queue_size` is the maximum number of frames that are received but not read.
+    pub fn into_dma_packet<CH>(
+        self,
+        dma_ch: CH,
+        buf_size: usize,
+        queue_size: usize,
+        timeout: MicrosDurationU32,
+    ) -> (
+        UartDmaPacketRx<U, CH, OS>,
+        UartDmaRxNotify<CH, OS>,
+        UartPacketIdleNotify<U, CH, OS>,
+    )
+    where
+        CH: DmaBindRx<U> + Steal,
+        OS: OsInterface,
+    {
+        UartDmaPacketRx::new(self.uart, dma_ch, buf_size, queue_size, timeout)
+    }
+
+    /// DMX512 receiver, the UART should be configured with [`Config::dmx512`].
+    /// Call [`DmxRxHandler::handler`] in the UART interrupt callback.
+    pub fn into_dmx<CH>(self, dma_ch: CH) -> (DmxRx, DmxRxHandler<U, CH>)
+    where
+        CH: DmaBindRx<U>,
+    {
+        DmxRx::new(self.uart, dma_ch)
+    }
 }
@@ -21,10 +21,7 @@ impl UartInit<UartX> for UartX {
 
 impl UartPeriphConfig for UartX {
     fn config(&mut self, config: Config) {
-        // Configure baud rate
-        let brr = self.get_clock().raw() / config.baudrate;
-        l::assert!(brr >= 16, "impossible baud rate");
-        self.brr().write(|w| unsafe { w.bits(brr as u16) });
+        self.set_baudrate(config.baudrate);
 
         // Configure word
         self.cr1().modify(|_, w| {
@@ -62,7 +59,7 @@ impl UartPeriphConfig for UartX {
 
         // StopBits::STOP0P5 and StopBits::STOP1P5 aren't supported when using UART
         // STOP_A::STOP1 and STOP_A::STOP2 will be used, respectively
-        self.cr2().write(|w| {
+        self.cr2().modify(|_, w| {
             w.stop().variant(match bits {
                 StopBits::STOP0P5 | StopBits::STOP1 => STOP::Stop1,
                 StopBits::STOP1P5 | StopBits::STOP2 => STOP::Stop2,
@@ -71,6 +68,10 @@ impl UartPeriphConfig for UartX {
         // $sync stop_bits_end
     }
 
+    fn set_half_duplex(&mut self, enable: bool) {
+        self.cr3().modify(|_, w| w.hdsel().bit(enable));
+    }
+
     #[inline]
     fn is_tx_empty(&self) -> bool {
         self.sr().read().txe().bit_is_set()
@@ -85,6 +86,12 @@ impl UartPeriphConfig for UartX {
 // Implement Peripheral -------------------------------------------------------
 
 impl UartPeriph for UartX {
+    fn set_baudrate(&mut self, baudrate: u32) {
+        let brr = self.get_clock().raw() / baudrate;
+        l::assert!(brr >= 16, "impossible baud rate");
+        self.brr().write(|w| unsafe { w.bits(brr as u16) });
+    }
+
     #[inline]
     fn is_tx_complete(&self) -> bool {
         let sr = self.sr().read();
@@ -116,7 +123,17 @@ impl UartPeriph for UartX {
         }
 
         // Check for any errors
-        let err = if sr.pe().bit_is_set() {
+        if let Some(err) = self.get_rx_error() {
+            self.clear_err_flag();
+            Err(nb::Error::Other(err))
+        } else {
+            Err(nb::Error::WouldBlock)
+        }
+    }
+
+    fn get_rx_error(&mut self) -> Option<Error> {
+        let sr = self.sr().read();
+        if sr.pe().bit_is_set() {
             Some(Error::Parity)
         } else if sr.fe().bit_is_set() {
             Some(Error::FrameFormat)
@@ -126,13 +143,19 @@ impl UartPeriph for UartX {
             Some(Error::Overrun)
         } else {
             None
-        };
+        }
+    }
 
-        if let Some(err) = err {
-            self.clear_err_flag();
-            Err(nb::Error::Other(err))
-        } else {
-            Err(nb::Error::WouldBlock)
+    fn get_status(&mut self) -> UartStatus {
+        let sr = self.sr().read();
+        let cr1 = self.cr1().read();
+        UartStatus {
+            rx_not_empty: sr.rxne().bit_is_set() && cr1.rxneie().bit_is_set(),
+            tx_empty: sr.txe().bit_is_set() && cr1.txeie().bit_is_set(),
+            tx_complete: sr.tc().bit_is_set() && cr1.tcie().bit_is_set(),
+            idle: sr.idle().bit_is_set() && cr1.idleie().bit_is_set(),
+            lin_break: sr.lbd().bit_is_set() && self.cr2().read().lbdie().bit_is_set(),
+            rx_error: self.get_rx_error(),
         }
     }
 
@@ -140,8 +163,12 @@ impl UartPeriph for UartX {
         self.cr1().modify(|_, w| {
             w.idleie().clear_bit();
             w.rxneie().clear_bit();
-            w.txeie().clear_bit()
+            w.txeie().clear_bit();
+            w.tcie().clear_bit();
+            w.peie().clear_bit()
         });
+        self.cr2().modify(|_, w| w.lbdie().clear_bit());
+        self.cr3().modify(|_, w| w.eie().clear_bit());
     }
 
     #[inline]
@@ -156,6 +183,16 @@ impl UartPeriph for UartX {
             Event::TxEmpty => {
                 self.cr1().modify(|_, w| w.txeie().bit(enable));
             }
+            Event::TxComplete => {
+                self.cr1().modify(|_, w| w.tcie().bit(enable));
+            }
+            Event::LinBreak => {
+                self.cr2().modify(|_, w| w.lbdie().bit(enable));
+            }
+            Event::RxError => {
+                self.cr1().modify(|_, w| w.peie().bit(enable));
+                self.cr3().modify(|_, w| w.eie().bit(enable));
+            }
         }
     }
 
@@ -166,6 +203,9 @@ impl UartPeriph for UartX {
             Event::Idle => cr1.idleie().bit_is_set(),
             Event::RxNotEmpty => cr1.rxneie().bit_is_set(),
             Event::TxEmpty => cr1.txeie().bit_is_set(),
+            Event::TxComplete => cr1.tcie().bit_is_set(),
+            Event::LinBreak => self.cr2().read().lbdie().bit_is_set(),
+            Event::RxError => cr1.peie().bit_is_set(),
         }
     }
 
@@ -191,6 +231,29 @@ impl UartPeriph for UartX {
                     return true;
                 }
             }
+            Event::TxComplete => {
+                if sr.tc().bit_is_set() && self.cr1().read().tcie().bit_is_set() {
+                    self.clear_tx_complete_flag();
+                    return true;
+                }
+            }
+            Event::LinBreak => {
+                if sr.lbd().bit_is_set() {
+                    self.sr().write(|w| w.lbd().clear_bit());
+                    return true;
+                }
+            }
+            Event::RxError => {
+                // The flags are cleared by reading the data register
+                if (sr.pe().bit_is_set()
+                    || sr.fe().bit_is_set()
+                    || sr.ne().bit_is_set()
+                    || sr.ore().bit_is_set())
+                    && self.cr1().read().peie().bit_is_set()
+                {
+                    return true;
+                }
+            }
         }
         false
     }
@@ -202,6 +265,76 @@ impl UartPeriph for UartX {
         let _ = self.sr().read();
         let _ = self.dr().read();
     }
+
+    #[inline]
+    fn clear_tx_complete_flag(&mut self) {
+        self.sr().write(|w| w.tc().clear_bit());
+    }
+
+    #[inline]
+    fn send_break(&mut self) {
+        self.cr1().modify(|_, w| w.sbk().set_bit());
+    }
+}
+
+impl LinPeriph for UartX {
+    fn set_lin_mode(&mut self, enable: bool, detection: LinBreakDetection) {
+        if enable {
+            // CLKEN, STOP, SCEN, HDSEL and IREN must be cleared in LIN mode
+            // $sync lin_mode_u4
+            // There is no clock output or smartcard mode
+            // $sync lin_mode_end
+            self.set_stop_bits(StopBits::STOP1);
+            self.cr3().modify(|_, w| {
+                w.hdsel().clear_bit();
+                w.iren().clear_bit()
+            });
+        }
+        self.cr2().modify(|_, w| {
+            w.lbdl().bit(detection == LinBreakDetection::Bits11);
+            w.linen().bit(enable)
+        });
+    }
+}
+
+impl UartPeriphMute for UartX {
+    fn set_wakeup(&mut self, method: WakeupMethod, address: u8) {
+        self.cr2()
+            .modify(|_, w| unsafe { w.add().bits(address & 0x0F) });
+        self.cr1()
+            .modify(|_, w| w.wake().bit(method == WakeupMethod::AddressMark));
+    }
+
+    #[inline]
+    fn enter_mute(&mut self) {
+        self.cr1().modify(|_, w| w.rwu().set_bit());
+    }
+
+    #[inline]
+    fn is_muted(&self) -> bool {
+        self.cr1().read().rwu().bit_is_set()
+    }
+}
+
+impl UartPeriphIrda for UartX {
+    fn set_irda(&mut self, enable: bool, mode: IrdaMode) {
+        if enable {
+            // The prescaler must be 1 in normal mode
+            let psc = match mode {
+                IrdaMode::Normal => 1,
+                IrdaMode::LowPower => {
+                    let psc =
+                        (self.get_clock().raw() + IRDA_LOW_POWER_FREQ / 2) / IRDA_LOW_POWER_FREQ;
+                    psc.clamp(1, u8::MAX as u32) as u8
+                }
+            };
+            self.gtpr().modify(|_, w| unsafe { w.psc().bits(psc) });
+        }
+        self.cr3().modify(|_, w| {
+            w.irlp().bit(enable && mode == IrdaMode::LowPower);
+            w.iren().bit(enable)
+        });
+    }
 }
 
 impl UartPeriphWithDma for UartX {